@@ -0,0 +1,25 @@
+//! Feeds arbitrary bytes to the instance parser: it must either reject them with
+//! an error, or produce an instance that can safely be used.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/error.rs"]
+mod error;
+#[allow(dead_code)]
+#[path = "../../src/instance.rs"]
+mod instance;
+#[allow(dead_code)]
+#[path = "../../src/postprocess.rs"]
+mod postprocess;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        if let Ok(instance) = instance::Instance::from_json(text) {
+            let _ = instance.costs();
+            let _ = instance.cluster_members();
+            let _ = instance.subset(&(0..instance.len()).rev().collect::<Vec<_>>());
+        }
+    }
+});
@@ -0,0 +1,26 @@
+//! Feeds arbitrary bytes to the parser of the real stops (csv, GeoJSON or instance):
+//! it must either reject them with an error, or produce a set of stops.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/error.rs"]
+mod error;
+#[allow(dead_code)]
+#[path = "../../src/instance.rs"]
+mod instance;
+#[allow(dead_code)]
+#[path = "../../src/postprocess.rs"]
+mod postprocess;
+#[allow(dead_code)]
+#[path = "../../src/stops.rs"]
+mod stops;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        if let Ok(stops) = stops::parse(text) {
+            assert!(!stops.is_empty());
+        }
+    }
+});
@@ -0,0 +1,21 @@
+//! Feeds arbitrary bytes to the tour parsers: they must either reject them with
+//! an error, or produce a tour.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/error.rs"]
+mod error;
+#[allow(dead_code)]
+#[path = "../../src/solution.rs"]
+mod solution;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(text) = std::str::from_utf8(data) {
+        if let Ok(solution) = solution::Solution::from_output(text) {
+            let _ = solution.to_text();
+        }
+        let _ = solution::Solution::parse(text);
+    }
+});
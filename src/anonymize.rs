@@ -0,0 +1,112 @@
+//! This module provides the command which turns a real (proprietary) instance into
+//! a shareable one: each destination is moved to a random point within a given
+//! radius, that point is snapped to the nearest routable location, and the travel
+//! costs are computed anew. The structure of the instance (clusters, optional
+//! waypoints, forbidden and required edges) is preserved, but the exact locations
+//! of the customers are not disclosed.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::Args;
+use osrm_client::{Client, Location};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use crate::{instance::{Instance, Metadata}, generation::GenerateInstance, report::Report, files, postprocess, sampling};
+
+/// The length of one degree of latitude (in metres)
+const METRES_PER_DEGREE: f64 = 111_320.0;
+
+/// This command jitters the destinations of an instance within a radius, snaps them
+/// to routable points and recomputes the travel cost matrix, so as to produce an
+/// instance with a similar structure but without the exact customer locations.
+#[derive(Debug, Args)]
+pub struct Anonymize {
+    /// The path to the instance file (`-` for the standard input)
+    #[clap(short, long)]
+    pub instance: String,
+    /// The radius (in metres) within which each destination is moved at random
+    #[clap(short, long, default_value="500")]
+    pub radius: f64,
+    /// An optional seed to make the jitter reproducible
+    #[clap(short, long)]
+    pub seed: Option<u64>,
+    /// Base the distance matrix on duration rather than distance
+    #[clap(short='D', long)]
+    pub duration: bool,
+    /// URL of the osrm server to use (optional)
+    #[clap(short, long)]
+    pub url_osrm: Option<String>,
+    /// Name of the file where to write the anonymized instance (`-` for the
+    /// standard output)
+    #[clap(short, long)]
+    pub output: Option<String>,
+    /// Overwrite the output file if it already exists
+    #[clap(long)]
+    pub force: bool,
+}
+
+impl Anonymize {
+    /// Executes this command
+    pub async fn execute(&self, report: &mut Report) {
+        if self.radius.is_nan() || self.radius <= 0.0 {
+            clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!(
+                "--radius ({}) must be positive\n", self.radius))
+                .exit();
+        }
+        if let Some(output) = self.output.as_ref() {
+            files::check_output(output, self.force);
+        }
+        let instance = files::read_instance(&self.instance);
+        let mut client = Client::default();
+        if let Some(url) = self.url_osrm.as_ref() {
+            client = client.base_url(url.clone());
+        }
+
+        let seed = self.seed.unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64);
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let mut destinations = vec![];
+        for location in instance.destinations.iter().copied() {
+            let jittered = self.jitter(&mut rng, location);
+            destinations.push(GenerateInstance::nearest(&client, jittered).await);
+        }
+        let mut distances = GenerateInstance::travel_cost_matrix(&client, &destinations, self.duration).await;
+        let completed = postprocess::complete(&mut distances)
+            .unwrap_or_else(|message| clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!(
+                "{message}: osrm finds no route to it (use another --seed or a smaller --radius)\n"))
+                .exit());
+
+        let anonymized = Instance {
+            destinations,
+            distances,
+            profiles: Default::default(),
+            overrides: vec![], // they are travel costs between the real locations
+            metadata: Metadata { completed, durations: self.duration, ..Metadata::default() },
+            ..instance
+        };
+        report.json("seed", seed);
+        report.json("instance", &self.instance);
+        let text = serde_json::to_string_pretty(&anonymized).unwrap();
+        if let Some(output) = self.output.as_ref() {
+            files::write_text(output, &text, self.force);
+            report.json("output", output);
+        } else {
+            report.field("anonymized", &anonymized, text);
+        }
+    }
+
+    /// Returns a point drawn uniformly at random in the disc of the given radius
+    /// around the given location
+    fn jitter(&self, rng: &mut impl Rng, Location{longitude, latitude}: Location) -> Location {
+        let (dx, dy) = loop {
+            let x = 2.0 * sampling::unit(rng) - 1.0;
+            let y = 2.0 * sampling::unit(rng) - 1.0;
+            if x * x + y * y <= 1.0 {
+                break (x * self.radius, y * self.radius);
+            }
+        };
+        let d_lat = dy / METRES_PER_DEGREE;
+        let d_lon = dx / (METRES_PER_DEGREE * sampling::cos((latitude as f64).to_radians()));
+        Location { longitude: longitude + d_lon as f32, latitude: latitude + d_lat as f32 }
+    }
+}
@@ -0,0 +1,237 @@
+//! This module provides the command which checks that a solution is a valid tour
+//! of an instance, and computes its actual cost.
+
+use clap::Args;
+
+use crate::{instance::{Instance, Variant}, report::Report, files, footprint::Footprint, resolution::local_search::tour_cost};
+
+/// This command checks that a solution visits each mandatory destination of an
+/// instance exactly once (and optional ones at most once) and reports its cost
+/// (taking the cost overrides into account).
+#[derive(Debug, Args)]
+pub struct Check {
+    /// The path to the instance file (`-` for the standard input)
+    #[clap(short, long)]
+    pub instance: String,
+    /// The solution to check (sequence of destination identifiers 0..n), or the path
+    /// of a file comprising it (`-` for the standard input)
+    #[clap(short, long)]
+    pub solution: String,
+    #[clap(flatten)]
+    pub footprint: Footprint,
+    /// If present, the length of the driver's shift (in hours): the check then
+    /// tells whether the tour fits within the working hours
+    #[clap(long)]
+    pub shift_hours: Option<f32>,
+    /// The time (in seconds) spent at each destination (but the depot)
+    #[clap(long, default_value="0")]
+    pub service_time: f32,
+    /// The path of an instance whose matrix holds the travel durations (in seconds)
+    /// between the destinations. It is needed by --shift-hours unless the instance
+    /// was generated with --duration.
+    #[clap(long)]
+    pub durations: Option<String>,
+}
+
+impl Check {
+    /// Executes this command
+    pub async fn execute(&self, report: &mut Report) {
+        let instance = files::read_instance(&self.instance);
+        let solution = files::read_solution(&self.solution);
+        let canonical = solution.clone().canonicalize();
+        let tour = solution.tour;
+
+        let violations = violations(&instance, &tour);
+        let valid = violations.is_empty();
+        report.json("instance", &self.instance);
+        let text = report.messages().boolean(valid);
+        report.row("valid", valid, text, "valid");
+        let value = valid.then(|| tour_cost(&instance.costs(), &tour) / 1000.0); // en kilometres
+        if let Some(value) = value {
+            report.row("value", value, format!("{value:.3}"), "value");
+        }
+
+        let shift = self.shift_hours.filter(|_| valid).map(|hours| {
+            let durations = durations(&instance, self.durations.as_deref())
+                .unwrap_or_else(|message| clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!("{message}\n")).exit());
+            let elapsed = shift_duration(&durations, &tour, self.service_time) / 3600.0;
+            let splits = shift_splits(&durations, &tour, self.service_time, hours * 3600.0);
+            let overlong = overlong_legs(&durations, &tour, self.service_time, hours * 3600.0);
+            let fits = elapsed <= hours;
+            report.row("working_hours", elapsed, format!("{elapsed:.2}"), "working-hours");
+            let text = report.messages().boolean(fits);
+            report.row("fits_shift", fits, text, "fits-shift");
+            (splits, overlong)
+        });
+
+        if let Some(value) = value {
+            self.footprint.report(report, value);
+        }
+        for v in violations.iter() {
+            let line = report.messages().format("violation", &[("value", v)]);
+            report.line(line);
+        }
+        report.json("violations", violations);
+        report.json("canonical", canonical.tour);
+
+        if let Some((splits, overlong)) = shift {
+            for k in splits.iter() {
+                let line = report.messages().format("split-needed", &[("destination", &tour[*k]), ("position", k)]);
+                report.line(line);
+            }
+            for k in overlong.iter() {
+                let (from, to) = (tour[*k], tour[(k + 1) % tour.len()]);
+                let line = report.messages().format("overlong-leg", &[("from", &from), ("to", &to), ("position", k)]);
+                report.line(line);
+            }
+            report.json("splits", splits);
+            report.json("overlong_legs", overlong);
+        }
+    }
+}
+
+/// Returns the travel durations (in seconds) between the destinations of the
+/// instance: those of the `durations` instance if one is given, or else the travel
+/// costs of the instance itself when they are durations. The error tells why no
+/// durations are available.
+pub fn durations(instance: &Instance, durations: Option<&str>) -> Result<Vec<Vec<f32>>, String> {
+    let Some(path) = durations else {
+        return if instance.metadata.durations {
+            Ok(instance.costs())
+        } else {
+            Err("the travel costs of the instance are distances, not durations: use --durations to give \
+                an instance generated with --duration".to_string())
+        };
+    };
+    let costs = files::read_instance(path).costs();
+    if costs.len() != instance.len() {
+        return Err(format!("the durations ({} destinations) do not match the instance ({} destinations)",
+            costs.len(), instance.len()));
+    }
+    Ok(costs)
+}
+
+/// Returns the time (in seconds) needed to drive along the (closed) tour and to
+/// serve each of its destinations but the depot
+pub fn shift_duration(durations: &[Vec<f32>], tour: &[usize], service: f32) -> f32 {
+    legs(tour).map(|(from, to)| step(durations, from, to, service)).sum()
+}
+
+/// Returns the time (in seconds) needed to drive from `from` to `to` and to serve
+/// the latter (unless it is the depot)
+fn step(durations: &[Vec<f32>], from: usize, to: usize, service: f32) -> f32 {
+    durations[from][to] + if to == 0 { 0.0 } else { service }
+}
+
+/// Returns the positions in the tour before which the work must be interrupted so
+/// that no shift lasts longer than `shift` seconds (the driver resumes the tour on
+/// the next shift from where it stopped). The return to the depot is part of the
+/// last shift. The result is empty when the whole tour fits in one shift; the legs
+/// which do not fit in any shift (see `overlong_legs`) are never split.
+pub fn shift_splits(durations: &[Vec<f32>], tour: &[usize], service: f32, shift: f32) -> Vec<usize> {
+    let mut splits = vec![];
+    let mut elapsed = 0.0;
+    for (k, (from, to)) in legs(tour).enumerate() {
+        let step = step(durations, from, to, service);
+        if elapsed > 0.0 && elapsed + step > shift {
+            splits.push((k + 1) % tour.len());
+            elapsed = 0.0;
+        }
+        elapsed += step;
+    }
+    splits
+}
+
+/// Returns the positions of the legs of the tour which, on their own, last longer
+/// than a shift of `shift` seconds (service time included)
+pub fn overlong_legs(durations: &[Vec<f32>], tour: &[usize], service: f32, shift: f32) -> Vec<usize> {
+    legs(tour).enumerate()
+        .filter(|(_, (from, to))| step(durations, *from, *to, service) > shift)
+        .map(|(k, _)| k)
+        .collect()
+}
+
+/// Returns the description of all the reasons why the given tour is not a valid
+/// solution of the instance (empty when the tour is valid)
+pub fn violations(instance: &Instance, tour: &[usize]) -> Vec<String> {
+    let n = instance.len();
+    let mut violations = vec![];
+    let mut visits = vec![0; n];
+    for x in tour.iter().copied() {
+        if x >= n {
+            violations.push(format!("destination {x} does not exist"));
+        } else {
+            visits[x] += 1;
+        }
+    }
+    if instance.variant == Variant::Gtsp {
+        violations.extend(cluster_violations(instance, &visits));
+    } else {
+        for (x, count) in visits.iter().copied().enumerate() {
+            match count {
+                0 if instance.is_optional(x) => {},
+                0 => violations.push(format!("destination {x} is never visited")),
+                1 => {},
+                _ => violations.push(format!("destination {x} is visited {count} times")),
+            }
+        }
+    }
+    for (from, to) in legs(tour) {
+        if instance.is_forbidden(from, to) {
+            violations.push(format!("the forbidden edge {from} -> {to} is used"));
+        }
+    }
+    for (from, to) in instance.required.iter().copied() {
+        if !legs(tour).any(|leg| leg == (from, to)) {
+            violations.push(format!("the required edge {from} -> {to} is not used"));
+        }
+    }
+    violations
+}
+
+/// Exits with an error listing the violations when the tour (read from `solution`)
+/// is not a valid tour of the instance (see `violations`)
+pub fn ensure_valid(instance: &Instance, tour: &[usize], solution: &str) {
+    let violations = violations(instance, tour);
+    if !violations.is_empty() {
+        clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!(
+            "{solution}: the tour is not valid for the instance: {}\n", violations.join(", ")))
+            .exit();
+    }
+}
+
+/// Returns the description of the clusters of a GTSP instance which are not
+/// visited exactly once (the cluster of the depot being visited by the depot)
+fn cluster_violations(instance: &Instance, visits: &[usize]) -> Vec<String> {
+    let mut violations = vec![];
+    for (k, ids) in instance.cluster_members().unwrap_or_default().iter().enumerate() {
+        let count = ids.iter().map(|i| visits[*i]).sum::<usize>();
+        match count {
+            0 => violations.push(format!("cluster {k} is never visited")),
+            1 => {},
+            _ => violations.push(format!("cluster {k} is visited {count} times")),
+        }
+    }
+    violations
+}
+
+/// Returns all the legs of the (closed) tour
+pub fn legs(tour: &[usize]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    tour.iter().copied().zip(tour.iter().copied().cycle().skip(1)).take(tour.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_distances_are_not_taken_as_durations() {
+        let mut instance = Instance::from_json(r#"{
+            "destinations": [{"longitude": 4.35, "latitude": 50.85}, {"longitude": 4.40, "latitude": 50.90}],
+            "distances": [[0, 5000], [5000, 0]]
+        }"#).unwrap();
+        assert!(durations(&instance, None).is_err());
+        instance.metadata.durations = true;
+        assert_eq!(durations(&instance, None), Ok(vec![vec![0.0, 5000.0], vec![5000.0, 0.0]]));
+    }
+}
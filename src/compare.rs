@@ -0,0 +1,247 @@
+//! This module provides the command which compares the spatial structure of an
+//! instance with that of a real dataset of stops, so as to check that the generator
+//! reproduces the structure of the real world. Three statistics are compared:
+//! - the distances from each stop to its nearest neighbour, by the two-sample
+//!   Kolmogorov-Smirnov statistic (the largest gap between both cumulative
+//!   distributions, 0 for identical samples and 1 at most),
+//! - the distances between all pairs of stops, by the Jensen-Shannon divergence of
+//!   their histograms (in bits, 0 for identical histograms and 1 at most),
+//! - the relative sizes of the clusters, by the total variation distance between
+//!   the sorted sizes (0 for identical sizes and 1 at most). The reference stops
+//!   have no cluster labels: they are clustered with k-means, k being the number
+//!   of clusters of the instance.
+//!
+//! All distances are great circle distances, since the reference stops have no
+//! travel cost matrix.
+
+use clap::Args;
+use osrm_client::Location;
+use serde::Serialize;
+
+use crate::{report::Report, files, elevation::haversine, stops::{self, Region}};
+
+/// The maximum number of iterations of the k-means clustering
+const KMEANS_ITERATIONS: usize = 100;
+
+/// This command compares the spatial statistics of an instance (nearest neighbour
+/// distances, pairwise distances, cluster sizes) with those of a reference dataset
+/// of real stops, and reports how much they diverge.
+#[derive(Debug, Args)]
+pub struct CompareDistribution {
+    /// The path to the instance file (`-` for the standard input)
+    #[clap(short, long)]
+    pub instance: String,
+    /// The path to the reference stops: an instance, or a csv file having one stop
+    /// per line (with lon/lat columns)
+    #[clap(short, long)]
+    pub reference: String,
+    /// The region (min_longitude,min_latitude,max_longitude,max_latitude) where the
+    /// stops are expected, which tells whether their longitude and latitude columns
+    /// have been swapped (by default, the default box of generate)
+    #[clap(long, value_parser=Region::parse, default_value=stops::DEFAULT_REGION)]
+    pub region: Region,
+    /// The number of bins of the histograms of the pairwise distances
+    #[clap(short, long, default_value="20", value_parser=clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub bins: usize,
+}
+
+/// The statistics of one set of stops
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    /// The number of stops
+    pub stops: usize,
+    /// The mean distance (in metres) from a stop to its nearest neighbour
+    pub mean_nearest: f64,
+    /// The mean distance (in metres) between two stops
+    pub mean_pairwise: f64,
+    /// The fraction of the stops in each cluster, from the largest cluster down
+    pub cluster_sizes: Vec<f64>,
+    /// The number of pairs of stops whose distance falls in each bin
+    pub histogram: Vec<usize>,
+}
+
+/// How much the statistics of the instance diverge from those of the reference
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Divergence {
+    /// Kolmogorov-Smirnov statistic of the nearest neighbour distances
+    pub nearest_ks: f64,
+    /// Jensen-Shannon divergence of the pairwise distances histograms (in bits)
+    pub pairwise_js: f64,
+    /// Total variation distance between the relative cluster sizes
+    pub cluster_tv: f64,
+}
+
+impl CompareDistribution {
+    /// Executes this command
+    pub async fn execute(&self, report: &mut Report) {
+        let instance = files::read_instance(&self.instance);
+        let reference = files::read_stops(&self.reference, &self.region);
+
+        let members = instance.cluster_members().unwrap_or_else(|| vec![(0..instance.len()).collect()]);
+        let mut labels = vec![0; instance.len()];
+        for (c, ids) in members.iter().enumerate() {
+            ids.iter().for_each(|i| labels[*i] = c);
+        }
+        let reference_labels = kmeans(&reference, members.len());
+
+        let ours = pairwise(&instance.destinations);
+        let theirs = pairwise(&reference);
+        let longest = ours.iter().chain(theirs.iter()).copied().fold(0.0, f64::max);
+
+        let summary = |stops: &[Location], pairs: &[f64], labels: &[usize]| Summary {
+            stops: stops.len(),
+            mean_nearest: mean(&nearest(stops)),
+            mean_pairwise: mean(pairs),
+            cluster_sizes: cluster_sizes(labels, members.len()),
+            histogram: histogram(pairs, longest, self.bins),
+        };
+        let ours = summary(&instance.destinations, &ours, &labels);
+        let theirs = summary(&reference, &theirs, &reference_labels);
+        let divergence = Divergence {
+            nearest_ks: kolmogorov_smirnov(&nearest(&instance.destinations), &nearest(&reference)),
+            pairwise_js: jensen_shannon(&ours.histogram, &theirs.histogram),
+            cluster_tv: 0.5 * ours.cluster_sizes.iter().zip(theirs.cluster_sizes.iter()).map(|(a, b)| (a - b).abs()).sum::<f64>(),
+        };
+
+        report.json("instance", &self.instance);
+        report.json("reference", &self.reference);
+        let messages = report.messages();
+        let lines = [
+            format!("{:<24} {:>12} {:>12}", "", messages.text("compare-instance"), messages.text("compare-reference")),
+            format!("{:<24} {:>12} {:>12}", messages.text("compare-stops"), ours.stops, theirs.stops),
+            format!("{:<24} {:>12.0} {:>12.0}", messages.text("compare-mean-nearest"), ours.mean_nearest, theirs.mean_nearest),
+            format!("{:<24} {:>12.0} {:>12.0}", messages.text("compare-mean-pairwise"), ours.mean_pairwise, theirs.mean_pairwise),
+            format!("{:<24} {:>12.2} {:>12.2}", messages.text("compare-largest-cluster"), ours.cluster_sizes[0], theirs.cluster_sizes[0]),
+        ];
+        let divergence_line = messages.format("divergence", &[("nearest", &format!("{:.3}", divergence.nearest_ks)),
+            ("pairwise", &format!("{:.3}", divergence.pairwise_js)), ("clusters", &format!("{:.3}", divergence.cluster_tv))]);
+        lines.into_iter().for_each(|line| report.line(line));
+        report.json("summary", serde_json::json!({"instance": ours, "reference": theirs}));
+        report.field("divergence", divergence, divergence_line);
+    }
+}
+
+/// Returns the distance from each stop to its nearest neighbour
+fn nearest(stops: &[Location]) -> Vec<f64> {
+    stops.iter().enumerate()
+        .map(|(i, a)| stops.iter().enumerate()
+            .filter(|(j, _)| *j != i)
+            .map(|(_, b)| haversine(*a, *b) as f64)
+            .fold(f64::INFINITY, f64::min))
+        .filter(|d| d.is_finite())
+        .collect()
+}
+
+/// Returns the distances between all pairs of stops
+fn pairwise(stops: &[Location]) -> Vec<f64> {
+    let mut result = vec![];
+    for (i, a) in stops.iter().enumerate() {
+        for b in stops[i + 1..].iter() {
+            result.push(haversine(*a, *b) as f64);
+        }
+    }
+    result
+}
+
+/// Returns the mean of the given values (0 if there are none)
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() { 0.0 } else { values.iter().sum::<f64>() / values.len() as f64 }
+}
+
+/// Returns the histogram of the given values, in `bins` bins evenly spanning 0..=longest
+fn histogram(values: &[f64], longest: f64, bins: usize) -> Vec<usize> {
+    let mut result = vec![0; bins];
+    for v in values {
+        let bin = if longest > 0.0 { (v / longest * bins as f64) as usize } else { 0 };
+        result[bin.min(bins - 1)] += 1;
+    }
+    result
+}
+
+/// Returns the fraction of the labels in each of the `k` clusters, from the largest
+/// cluster down
+fn cluster_sizes(labels: &[usize], k: usize) -> Vec<f64> {
+    let mut sizes = vec![0.0; k];
+    labels.iter().for_each(|c| sizes[*c] += 1.0);
+    let total = labels.len().max(1) as f64;
+    sizes.iter_mut().for_each(|s| *s /= total);
+    sizes.sort_by(|a, b| b.total_cmp(a));
+    sizes
+}
+
+/// Returns the two-sample Kolmogorov-Smirnov statistic of the given samples
+fn kolmogorov_smirnov(a: &[f64], b: &[f64]) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 1.0;
+    }
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort_by(f64::total_cmp);
+    b.sort_by(f64::total_cmp);
+    let (mut i, mut j, mut gap) = (0, 0, 0.0_f64);
+    while i < a.len() && j < b.len() {
+        let x = a[i].min(b[j]);
+        while i < a.len() && a[i] <= x { i += 1; }
+        while j < b.len() && b[j] <= x { j += 1; }
+        gap = gap.max((i as f64 / a.len() as f64 - j as f64 / b.len() as f64).abs());
+    }
+    gap
+}
+
+/// Returns the Jensen-Shannon divergence (in bits) of the given histograms
+fn jensen_shannon(a: &[usize], b: &[usize]) -> f64 {
+    let total = |h: &[usize]| h.iter().sum::<usize>().max(1) as f64;
+    let (ta, tb) = (total(a), total(b));
+    let kl = |p: f64, m: f64| if p > 0.0 { p * (p / m).log2() } else { 0.0 };
+    a.iter().zip(b.iter())
+        .map(|(x, y)| {
+            let (p, q) = (*x as f64 / ta, *y as f64 / tb);
+            let m = (p + q) / 2.0;
+            0.5 * kl(p, m) + 0.5 * kl(q, m)
+        })
+        .sum()
+}
+
+/// Partitions the stops in `k` clusters with the k-means algorithm (on the plane of
+/// the longitudes and latitudes). The initial centers are picked deterministically
+/// by farthest point: the first stop, then repeatedly the stop which lies the
+/// farthest from all the centers picked so far.
+pub fn kmeans(stops: &[Location], k: usize) -> Vec<usize> {
+    let k = k.clamp(1, stops.len());
+    let point = |l: &Location| (l.longitude as f64, l.latitude as f64);
+    let distance = |a: (f64, f64), b: (f64, f64)| (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2);
+    let closest = |p: (f64, f64), centers: &[(f64, f64)]| (0..centers.len())
+        .min_by(|x, y| distance(p, centers[*x]).total_cmp(&distance(p, centers[*y])))
+        .unwrap();
+
+    let mut centers = vec![point(&stops[0])];
+    while centers.len() < k {
+        let farthest = stops.iter()
+            .map(point)
+            .max_by(|a, b| {
+                let da = centers.iter().map(|c| distance(*a, *c)).fold(f64::INFINITY, f64::min);
+                let db = centers.iter().map(|c| distance(*b, *c)).fold(f64::INFINITY, f64::min);
+                da.total_cmp(&db)
+            })
+            .unwrap();
+        centers.push(farthest);
+    }
+
+    let mut labels = vec![0; stops.len()];
+    for _ in 0..KMEANS_ITERATIONS {
+        let next = stops.iter().map(|s| closest(point(s), &centers)).collect::<Vec<_>>();
+        let stable = next == labels;
+        labels = next;
+        for (c, center) in centers.iter_mut().enumerate() {
+            let members = stops.iter().zip(labels.iter()).filter(|(_, l)| **l == c).map(|(s, _)| point(s)).collect::<Vec<_>>();
+            if !members.is_empty() {
+                let n = members.len() as f64;
+                *center = (members.iter().map(|p| p.0).sum::<f64>() / n, members.iter().map(|p| p.1).sum::<f64>() / n);
+            }
+        }
+        if stable {
+            break;
+        }
+    }
+    labels
+}
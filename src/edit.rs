@@ -0,0 +1,163 @@
+//! This module provides the commands which edit an existing instance in place.
+
+use clap::Args;
+
+use crate::{instance::Instance, report::Report, files};
+
+/// The arguments shared by all the commands which edit one edge of an instance
+#[derive(Debug, Args)]
+pub struct EdgeEdit {
+    /// The path to the instance file
+    #[clap(short, long)]
+    pub instance: String,
+    /// The destination the edge leaves from
+    #[clap(short, long)]
+    pub from: usize,
+    /// The destination the edge leads to
+    #[clap(short, long)]
+    pub to: usize,
+    /// Also apply the change to the edge going in the opposite direction
+    #[clap(short, long)]
+    pub symmetric: bool,
+    /// If present, the path where to write the edited instance (by default, the
+    /// instance file is edited in place)
+    #[clap(short, long)]
+    pub output: Option<String>,
+    /// Overwrite the output file if it already exists
+    #[clap(long)]
+    pub force: bool,
+}
+
+impl EdgeEdit {
+    /// Loads the instance to edit
+    fn load(&self) -> Instance {
+        if let Some(output) = self.output.as_ref().filter(|o| **o != self.instance) {
+            files::check_output(output, self.force);
+        }
+        let instance = files::read_instance(&self.instance);
+        let n = instance.len();
+        if let Some(d) = [self.from, self.to].into_iter().find(|d| *d >= n) {
+            clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!(
+                "{}: there is no destination {d}, the instance only has {n} destinations (0..{n})\n", self.instance))
+                .exit();
+        }
+        instance
+    }
+
+    /// Returns the edges affected by the edit
+    fn edges(&self) -> Vec<(usize, usize)> {
+        if self.symmetric {
+            vec![(self.from, self.to), (self.to, self.from)]
+        } else {
+            vec![(self.from, self.to)]
+        }
+    }
+
+    /// Saves the edited instance. The instance file itself is always overwritten
+    /// since it is edited in place, but another existing output file is only
+    /// overwritten with --force.
+    fn save(&self, instance: &Instance, report: &mut Report) {
+        let output = self.output.as_ref().unwrap_or(&self.instance);
+        let text = serde_json::to_string_pretty(instance).unwrap();
+        files::write_text(output, &text, self.force || *output == self.instance);
+        report.json("output", output);
+    }
+}
+
+/// This command sets (or resets) a custom travel cost for one edge of an instance,
+/// e.g. to model a closed road or a penalized connection without regenerating it.
+#[derive(Debug, Args)]
+pub struct SetCost {
+    #[command(flatten)]
+    pub edge: EdgeEdit,
+    /// The custom cost of the edge (in the units of the matrix)
+    #[clap(short, long, required_unless_present="reset")]
+    pub cost: Option<f32>,
+    /// Remove the custom cost of the edge instead of setting it
+    #[clap(short, long, conflicts_with="cost")]
+    pub reset: bool,
+}
+
+impl SetCost {
+    /// Executes this command
+    pub async fn execute(&self, report: &mut Report) {
+        self.check_cost();
+        let mut instance = self.edge.load();
+        for (from, to) in self.edge.edges() {
+            match self.cost {
+                Some(cost) if !self.reset => instance.set_cost(from, to, cost),
+                _ => instance.reset_cost(from, to),
+            }
+        }
+        self.edge.save(&instance, report);
+        let line = report.messages().format("overrides", &[("value", &instance.overrides.len())]);
+        report.field("overrides", &instance.overrides, line);
+    }
+
+    /// Exits with an error when the custom cost is not a finite, non negative
+    /// number (the edited instance would be rejected when it is read back)
+    fn check_cost(&self) {
+        match self.cost {
+            Some(cost) if !self.reset && (!cost.is_finite() || cost < 0.0) => {
+                clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!(
+                    "--cost ({cost}) must be a finite, non negative number: use forbid to prevent travelling along an edge\n"))
+                    .exit();
+            },
+            _ => {},
+        }
+    }
+}
+
+/// This command requires (or stops requiring) one edge to be part of any tour of an
+/// instance, e.g. to reproduce a partially fixed operational plan.
+#[derive(Debug, Args)]
+pub struct Require {
+    #[command(flatten)]
+    pub edge: EdgeEdit,
+    /// Stop requiring the edge instead of requiring it
+    #[clap(short, long)]
+    pub reset: bool,
+}
+
+impl Require {
+    /// Executes this command
+    pub async fn execute(&self, report: &mut Report) {
+        let mut instance = self.edge.load();
+        for edge in self.edge.edges() {
+            instance.required.retain(|e| *e != edge);
+            if !self.reset {
+                instance.required.push(edge);
+            }
+        }
+        self.edge.save(&instance, report);
+        let line = report.messages().format("required", &[("value", &instance.required.len())]);
+        report.field("required", &instance.required, line);
+    }
+}
+
+/// This command forbids (or allows again) travelling along one edge of an instance,
+/// e.g. to model a one-way restriction or a business rule.
+#[derive(Debug, Args)]
+pub struct Forbid {
+    #[command(flatten)]
+    pub edge: EdgeEdit,
+    /// Allow the edge again instead of forbidding it
+    #[clap(short, long)]
+    pub reset: bool,
+}
+
+impl Forbid {
+    /// Executes this command
+    pub async fn execute(&self, report: &mut Report) {
+        let mut instance = self.edge.load();
+        for edge in self.edge.edges() {
+            instance.forbidden.retain(|e| *e != edge);
+            if !self.reset {
+                instance.forbidden.push(edge);
+            }
+        }
+        self.edge.save(&instance, report);
+        let line = report.messages().format("forbidden", &[("value", &instance.forbidden.len())]);
+        report.field("forbidden", &instance.forbidden, line);
+    }
+}
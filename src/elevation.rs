@@ -0,0 +1,87 @@
+//! This module retrieves the elevation of locations from an elevation service which
+//! implements the Open-Elevation api (`POST {url}/api/v1/lookup`). Such a service
+//! is a prerequisite of the features that care about the climbs along a route.
+
+use osrm_client::Location;
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+
+/// The maximum number of locations which are sent in one single request
+const BATCH_SIZE: usize = 100;
+
+/// One point of the elevation profile of a route
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ProfilePoint {
+    /// The distance (in metres) travelled from the start of the route
+    pub distance: f32,
+    /// The elevation (in metres) at that point
+    pub elevation: f32,
+    pub longitude: f32,
+    pub latitude: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct LookupResponse {
+    results: Vec<LookupResult>,
+}
+#[derive(Debug, Deserialize)]
+struct LookupResult {
+    elevation: f32,
+}
+
+/// Returns the elevation (in metres) of each of the given locations. The error
+/// tells why the elevation service could not provide them.
+pub async fn elevations(url: &str, locations: &[Location]) -> Result<Vec<f32>, String> {
+    let client = reqwest::Client::new();
+    let mut result = vec![];
+    for batch in locations.chunks(BATCH_SIZE) {
+        let body = json!({
+            "locations": batch.iter()
+                .map(|l| json!({"latitude": l.latitude, "longitude": l.longitude}))
+                .collect::<Vec<_>>()
+        });
+        let response: LookupResponse = client.post(format!("{url}/api/v1/lookup"))
+            .json(&body)
+            .send().await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("cannot reach the elevation service {url}: {e}"))?
+            .json().await
+            .map_err(|e| format!("the elevation service {url} sent an invalid response: {e}"))?;
+        result.extend(response.results.iter().map(|r| r.elevation));
+    }
+    Ok(result)
+}
+
+/// Returns the elevation profile along the given path, which is sampled at (at
+/// most) `samples` evenly spaced points (see `elevations` for the error)
+pub async fn profile(url: &str, path: &[Location], samples: usize) -> Result<Vec<ProfilePoint>, String> {
+    let mut travelled = vec![0.0];
+    for leg in path.windows(2) {
+        travelled.push(travelled.last().unwrap() + haversine(leg[0], leg[1]));
+    }
+    let step = (path.len() / samples.max(1)).max(1);
+    let mut picked = (0..path.len()).step_by(step).chain(path.len().checked_sub(1))
+        .collect::<Vec<_>>();
+    picked.dedup();
+
+    let locations = picked.iter().map(|i| path[*i]).collect::<Vec<_>>();
+    let heights = elevations(url, &locations).await?;
+    Ok(picked.iter().zip(heights)
+        .map(|(i, elevation)| ProfilePoint {
+            distance: travelled[*i],
+            elevation,
+            longitude: path[*i].longitude,
+            latitude: path[*i].latitude,
+        })
+        .collect())
+}
+
+/// Returns the great circle distance (in metres) between two locations
+pub fn haversine(a: Location, b: Location) -> f32 {
+    const EARTH_RADIUS: f64 = 6_371_000.0;
+    let (lat_a, lat_b) = ((a.latitude as f64).to_radians(), (b.latitude as f64).to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b.longitude as f64 - a.longitude as f64).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    (2.0 * EARTH_RADIUS * h.sqrt().asin()) as f32
+}
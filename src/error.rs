@@ -0,0 +1,44 @@
+//! This module defines the errors which are detected when reading the inputs of the
+//! commands. These inputs are often produced by other programs: a malformed input
+//! must be reported as such rather than crash the tool.
+
+use std::fmt::{self, Display};
+
+/// The reasons why an input cannot be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input cannot be read at all (missing file, permissions, not utf-8...)
+    Io(String),
+    /// The input is not a well formed json document of the expected shape
+    Json(String),
+    /// Some token is not a destination identifier
+    Token(String),
+    /// The input is well formed but it is inconsistent (e.g. a distance matrix
+    /// which is not square, or an edge leading to a nonexistent destination)
+    Invalid(String),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Io(e) => write!(f, "cannot read the input: {e}"),
+            ParseError::Json(e) => write!(f, "malformed json: {e}"),
+            ParseError::Token(t) => write!(f, "'{t}' is not a destination identifier"),
+            ParseError::Invalid(e) => write!(f, "invalid input: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<std::io::Error> for ParseError {
+    fn from(e: std::io::Error) -> Self {
+        ParseError::Io(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ParseError {
+    fn from(e: serde_json::Error) -> Self {
+        ParseError::Json(e.to_string())
+    }
+}
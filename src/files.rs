@@ -0,0 +1,151 @@
+//! This module gathers the utilities used to read the inputs and write the outputs
+//! of the various commands. In all of these, the path `-` denotes the standard
+//! input (when reading) or the standard output (when writing), which makes it
+//! possible to chain several commands in a pipeline.
+//!
+//! Outputs are always written atomically: the content is first written to a
+//! temporary file which is then renamed to its final name. An existing file is
+//! never overwritten unless this is explicitly requested.
+
+use std::{fs::{self, File}, io::{BufRead, BufReader, Read, Write}, path::Path, sync::atomic::{AtomicBool, Ordering}};
+
+use osrm_client::Location;
+
+use crate::{instance::Instance, manifest::Manifest, solution::Solution, error::ParseError, stops::{self, Region}};
+
+/// The path denoting the standard input or output
+pub const STDIO: &str = "-";
+
+/// Whether some output has been written to the standard output
+static STDOUT_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Returns true iff some output has been written to the standard output, which
+/// must then not be mixed with anything else
+pub fn stdout_taken() -> bool {
+    STDOUT_TAKEN.load(Ordering::Relaxed)
+}
+
+/// Opens the given path for reading
+pub fn reader(path: &str) -> Result<Box<dyn BufRead>, ParseError> {
+    if path == STDIO {
+        Ok(Box::new(BufReader::new(std::io::stdin())))
+    } else {
+        Ok(Box::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+/// Reads the complete content of the given path
+pub fn read_text(path: &str) -> Result<String, ParseError> {
+    let mut text = String::new();
+    reader(path)?.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Reports that the given input cannot be read, and terminates the process
+fn fail(input: &str, error: ParseError) -> ! {
+    eprintln!("error: {input}: {error}");
+    std::process::exit(2)
+}
+
+/// Checks that the given output can be written: unless `force` is set, an existing
+/// file is never overwritten. The commands call this before doing any work, so
+/// that they do not fail once their results are computed. The process terminates
+/// with an error message when the output cannot be written.
+pub fn check_output(path: &str, force: bool) {
+    if path != STDIO && !force && Path::new(path).exists() {
+        eprintln!("error: refusing to overwrite the existing file {path} (use --force to overwrite it)");
+        std::process::exit(2)
+    }
+}
+
+/// Writes the given text to the given path. Unless `force` is set, this refuses to
+/// overwrite an existing file (see `check_output`). The process terminates with an
+/// error message when the file cannot be written.
+pub fn write_text(path: &str, text: &str, force: bool) {
+    if path == STDIO {
+        STDOUT_TAKEN.store(true, Ordering::Relaxed);
+        std::io::stdout().write_all(text.as_bytes()).unwrap();
+        return;
+    }
+    check_output(path, force);
+
+    let tmp = format!("{path}.tmp-{}", std::process::id());
+    let written = File::create(&tmp).and_then(|mut file| {
+        file.write_all(text.as_bytes())?;
+        file.sync_all()
+    });
+    if let Err(error) = written.and_then(|_| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        eprintln!("error: cannot write {path}: {error}");
+        std::process::exit(2)
+    }
+}
+
+/// Reads an instance from the given path. The instance may either be stored as is,
+/// or be the `instance` field of the json document printed by `generate --json`.
+/// The process terminates with an error message when the instance is malformed.
+pub fn read_instance(path: &str) -> Instance {
+    try_read_instance(path).unwrap_or_else(|e| fail(path, e))
+}
+
+/// Reads an instance from the given path (see `read_instance`)
+pub fn try_read_instance(path: &str) -> Result<Instance, ParseError> {
+    Instance::from_json(&read_text(path)?)
+}
+
+/// Reads the manifest of a suite of instances. The process terminates with an error
+/// message when the manifest is malformed, or when some instance it lists is missing.
+pub fn read_manifest(path: &str) -> Manifest {
+    try_read_manifest(path).unwrap_or_else(|e| fail(path, e))
+}
+
+/// Reads the manifest of a suite of instances (see `read_manifest`)
+pub fn try_read_manifest(path: &str) -> Result<Manifest, ParseError> {
+    let manifest: Manifest = serde_json::from_str(&read_text(path)?)?;
+    if let Some(missing) = manifest.instance_paths(path).iter().find(|p| !Path::new(p).is_file()) {
+        return Err(ParseError::Invalid(format!("the instance {missing} is missing")));
+    }
+    Ok(manifest)
+}
+
+/// Reads a solution. The argument either is a sequence of destination identifiers,
+/// or the path (possibly `-`) to a file comprising such a sequence. That file may
+/// also be the output of the `solve` command (in any format). The process
+/// terminates with an error message when the solution is malformed.
+pub fn read_solution(arg: &str) -> Solution {
+    try_read_solution(arg).unwrap_or_else(|e| fail(arg, e))
+}
+
+/// Reads a solution (see `read_solution`)
+pub fn try_read_solution(arg: &str) -> Result<Solution, ParseError> {
+    if arg == STDIO || Path::new(arg).is_file() {
+        Solution::from_output(&read_text(arg)?)
+    } else {
+        Solution::parse(arg)
+    }
+}
+
+/// Reads a set of stops (locations). The input either is an instance (json), a
+/// GeoJSON feature collection (whose points are the stops), or a csv file having
+/// one stop per line. The columns holding the longitude and the
+/// latitude are found by name in the header line (lon/lng/longitude, lat/latitude)
+/// or, without header, they are the first two columns. The process terminates
+/// with an error message when the stops are malformed.
+///
+/// When the longitude and latitude columns seem swapped, they are exchanged back
+/// with a warning. This is the case when the coordinates are out of range, or when
+/// the swapped stops fit better in the region where the stops are expected (see
+/// `stops::orient`).
+pub fn read_stops(path: &str, region: &Region) -> Vec<Location> {
+    try_read_stops(path, region).unwrap_or_else(|e| fail(path, e))
+}
+
+/// Reads a set of stops from the given path (see `read_stops`)
+pub fn try_read_stops(path: &str, region: &Region) -> Result<Vec<Location>, ParseError> {
+    let (stops, swapped) = stops::orient(stops::parse(&read_text(path)?)?, region)?;
+    if swapped {
+        eprintln!("warning: {path}: the longitude and latitude columns seem swapped (the swapped stops fit \
+            the expected region, see --region): they have been exchanged");
+    }
+    Ok(stops)
+}
@@ -0,0 +1,163 @@
+//! This module provides the command which estimates the generation parameters
+//! from a dataset of real stops, so that the generator produces synthetic instances
+//! having the same structure without disclosing the real locations.
+//!
+//! The stops are modelled as a mixture of isotropic gaussians sharing the same std
+//! deviation, just like the generator samples them. For each number of clusters k
+//! (up to a maximum), the stops are partitioned with k-means and the maximum
+//! likelihood estimate of the std deviation is computed. The number of clusters
+//! which minimizes the Bayesian information criterion is retained. The stops which
+//! lie farther than three std deviations from their center are deemed outliers:
+//! they are counted, and they are left out of the final estimate of the deviation.
+
+use std::f64::consts::PI;
+
+use clap::Args;
+use osrm_client::Location;
+use serde::Serialize;
+
+use crate::{report::Report, files, compare::kmeans, stops::{self, Region}};
+
+/// The smallest std deviation (in degrees) which is estimated, lest clusters made
+/// of one single stop get an infinite likelihood
+const MIN_STD_DEV: f64 = 1e-4;
+/// The distance (in std deviations) from its center beyond which a stop is an outlier
+const OUTLIER_DISTANCE: f64 = 3.0;
+
+/// This command estimates the parameters of the generator (number and spread of
+/// the clusters, bounding box) from a dataset of real stops, and emits the
+/// generation config producing statistically similar instances.
+#[derive(Debug, Args)]
+pub struct Fit {
+    /// The path to the real stops: a csv file having one stop per line (with
+    /// lon/lat columns), a GeoJSON feature collection or an instance
+    #[clap(short, long)]
+    pub stops: String,
+    /// The largest number of clusters which is considered
+    #[clap(short='k', long, default_value="20")]
+    pub max_clusters: usize,
+    /// The region (min_longitude,min_latitude,max_longitude,max_latitude) where the
+    /// stops are expected, which tells whether their longitude and latitude columns
+    /// have been swapped (by default, the default box of generate)
+    #[clap(long, value_parser=Region::parse, default_value=stops::DEFAULT_REGION)]
+    pub region: Region,
+    /// If present, the path where to write the generation config (as a json document)
+    #[clap(short, long)]
+    pub output: Option<String>,
+    /// Overwrite the output file if it already exists
+    #[clap(long)]
+    pub force: bool,
+}
+
+/// The parameters of the generate command which produce instances similar to
+/// the real stops (the fields are named after the options of that command)
+#[derive(Debug, Clone, Serialize)]
+pub struct GenerationConfig {
+    pub nb_cities: usize,
+    pub nb_centroids: usize,
+    pub std_dev: f32,
+    pub min_longitude: f32,
+    pub max_longitude: f32,
+    pub min_latitude: f32,
+    pub max_latitude: f32,
+    /// The fraction of the real stops which are outliers (the generator does not
+    /// produce any)
+    pub outlier_fraction: f64,
+}
+
+impl GenerationConfig {
+    /// Returns the command line generating instances with this config
+    pub fn command_line(&self) -> String {
+        format!("tsptools generate --nb-cities {} --nb-centroids {} --std-dev {} \
+            --min-longitude={} --max-longitude={} --min-latitude={} --max-latitude={}",
+            self.nb_cities, self.nb_centroids, self.std_dev,
+            self.min_longitude, self.max_longitude, self.min_latitude, self.max_latitude)
+    }
+}
+
+impl Fit {
+    /// Executes this command
+    pub async fn execute(&self, report: &mut Report) {
+        if let Some(output) = self.output.as_ref() {
+            files::check_output(output, self.force);
+        }
+        let stops = files::read_stops(&self.stops, &self.region);
+        let points = stops.iter().map(|l: &Location| (l.longitude as f64, l.latitude as f64)).collect::<Vec<_>>();
+
+        let (k, labels, bic) = (1..=self.max_clusters.clamp(1, stops.len()))
+            .map(|k| {
+                let labels = kmeans(&stops, k);
+                let bic = bic(&points, &labels, k);
+                (k, labels, bic)
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .unwrap();
+
+        let centers = centers(&points, &labels, k);
+        let sigma = std_dev(&points, &labels, &centers, |_| true);
+        let inlier = |i: usize| distance(points[i], centers[labels[i]]) <= OUTLIER_DISTANCE * sigma;
+        let outliers = (0..points.len()).filter(|i| !inlier(*i)).count();
+        let sigma = std_dev(&points, &labels, &centers, inlier);
+
+        let (lon, lat) = (centers.iter().map(|c| c.0), centers.iter().map(|c| c.1));
+        let config = GenerationConfig {
+            nb_cities: stops.len(),
+            nb_centroids: k,
+            std_dev: sigma as f32,
+            min_longitude: (lon.clone().fold(f64::INFINITY, f64::min) - sigma) as f32,
+            max_longitude: (lon.fold(f64::NEG_INFINITY, f64::max) + sigma) as f32,
+            min_latitude: (lat.clone().fold(f64::INFINITY, f64::min) - sigma) as f32,
+            max_latitude: (lat.fold(f64::NEG_INFINITY, f64::max) + sigma) as f32,
+            outlier_fraction: outliers as f64 / stops.len() as f64,
+        };
+
+        report.json("stops", &self.stops);
+        report.json("bic", bic);
+        let line = report.messages().format("fit-summary", &[("stops", &stops.len()), ("clusters", &k),
+            ("sigma", &format!("{sigma:.5}")), ("outliers", &outliers)]);
+        report.line(line);
+        report.field("config", &config, config.command_line());
+        if let Some(output) = self.output.as_ref() {
+            files::write_text(output, &serde_json::to_string_pretty(&config).unwrap(), self.force);
+            report.json("output", output);
+        }
+    }
+}
+
+/// Returns the euclidean distance between two points (in degrees)
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+/// Returns the center (mean) of each of the `k` clusters
+fn centers(points: &[(f64, f64)], labels: &[usize], k: usize) -> Vec<(f64, f64)> {
+    let mut sums = vec![(0.0, 0.0, 0.0); k];
+    for (p, c) in points.iter().zip(labels.iter()) {
+        sums[*c] = (sums[*c].0 + p.0, sums[*c].1 + p.1, sums[*c].2 + 1.0);
+    }
+    sums.iter().map(|(x, y, n)| if *n > 0.0 { (x / n, y / n) } else { (0.0, 0.0) }).collect()
+}
+
+/// Returns the maximum likelihood estimate of the std deviation (per axis) shared by
+/// all clusters, only the points satisfying `keep` being considered
+fn std_dev(points: &[(f64, f64)], labels: &[usize], centers: &[(f64, f64)], keep: impl Fn(usize) -> bool) -> f64 {
+    let kept = (0..points.len()).filter(|i| keep(*i)).collect::<Vec<_>>();
+    let squares = kept.iter().map(|i| distance(points[*i], centers[labels[*i]]).powi(2)).sum::<f64>();
+    (squares / (2.0 * kept.len().max(1) as f64)).sqrt().max(MIN_STD_DEV)
+}
+
+/// Returns the Bayesian information criterion of the mixture of `k` isotropic
+/// gaussians sharing the same std deviation, given the partition of the points
+fn bic(points: &[(f64, f64)], labels: &[usize], k: usize) -> f64 {
+    let n = points.len() as f64;
+    let centers = centers(points, labels, k);
+    let sigma = std_dev(points, labels, &centers, |_| true);
+    let mut sizes = vec![0.0; k];
+    labels.iter().for_each(|c| sizes[*c] += 1.0);
+    let squares = points.iter().zip(labels.iter()).map(|(p, c)| distance(*p, centers[*c]).powi(2)).sum::<f64>();
+
+    let weights = sizes.iter().filter(|s| **s > 0.0).map(|s| s * (s / n).ln()).sum::<f64>();
+    let likelihood = weights - n * (2.0 * PI * sigma * sigma).ln() - squares / (2.0 * sigma * sigma);
+    let parameters = (3 * k) as f64; // the centers, the weights (but one) and the deviation
+    parameters * n.ln() - 2.0 * likelihood
+}
@@ -0,0 +1,43 @@
+//! This module computes the monetary cost and the carbon emissions of a tour from
+//! per kilometre factors, which logistics stakeholders want to see alongside the
+//! travelled distance.
+
+use clap::Args;
+
+use crate::report::Report;
+
+/// The per kilometre factors used to assess the footprint of a tour
+#[derive(Debug, Clone, Default, Args)]
+pub struct Footprint {
+    /// If present, the monetary cost of travelling one kilometre
+    #[clap(long)]
+    pub cost_per_km: Option<f32>,
+    /// If present, the mass of CO2 (in kg) emitted when travelling one kilometre
+    #[clap(long)]
+    pub co2_per_km: Option<f32>,
+}
+
+impl Footprint {
+    /// Returns the monetary cost of travelling the given number of kilometres
+    pub fn cost(&self, km: f32) -> Option<f32> {
+        self.cost_per_km.map(|factor| factor * km)
+    }
+
+    /// Returns the emissions (in kg of CO2) of travelling the given number of kilometres
+    pub fn co2(&self, km: f32) -> Option<f32> {
+        self.co2_per_km.map(|factor| factor * km)
+    }
+
+    /// Records the cost and emissions of travelling the given number of kilometres
+    /// in the report (for the factors which have been given)
+    pub fn report(&self, report: &mut Report, km: f32) {
+        if let Some(cost) = self.cost(km) {
+            let line = report.messages().format("cost-value", &[("value", &format!("{cost:.2}"))]);
+            report.field("cost", cost, line);
+        }
+        if let Some(co2) = self.co2(km) {
+            let line = report.messages().format("co2-value", &[("value", &format!("{co2:.2}"))]);
+            report.field("co2_kg", co2, line);
+        }
+    }
+}
@@ -1,16 +1,26 @@
 //! This module comprises all utilities that are required to generate a random TSP instance
 //! between places in the real world.
 
-use std::{time::{SystemTime, UNIX_EPOCH}, fs::File, io::Write};
+use std::{collections::BTreeMap, fmt::Display, time::{SystemTime, UNIX_EPOCH}, path::Path};
 
 use clap::Args;
+use futures::StreamExt;
 use osrm_client::{Location, NearestRequestBuilder, TableRequestBuilder, TableAnnotationRequest, Client};
 use rand::{Rng, SeedableRng};
-use rand_chacha::ChaChaRng;
-use rand_distr::{Uniform, Normal, Distribution};
+use rand_chacha::ChaCha20Rng;
+use serde::Serialize;
 
-use crate::instance::Instance;
+use crate::{instance::{Instance, Metadata, Postprocessing, SnapStats, Variant}, report::{Report, Format}, manifest::{Manifest, Entry}, presets::{self, Preset}, visualisation::{self, Palette}, files, project::Project, sampling, postprocess::{self, Symmetrize}, elevation::haversine, geocoding};
 
+/// The largest ratio between a side of the bounding box and the std deviation of the
+/// cities around their centroid. Beyond it, the clusters are mere dots lost in the
+/// box and the cities of different clusters can hardly be routed between.
+const MAX_SPREAD: f32 = 1000.0;
+/// The number of times a location is resampled because it lies too far from any
+/// road, before giving up
+const MAX_SNAP_ATTEMPTS: usize = 100;
+/// The length of one degree of latitude (in kilometres)
+const KM_PER_DEGREE: f32 = 111.32;
 
 /// TspGen is a generator for realistic TSP instances where the cities to visit are gouped in clusters.
 /// 
@@ -18,7 +28,7 @@ use crate::instance::Instance;
 /// ```
 /// ./target/release/tspgen  --min-longitude=2.376776  --max-longitude=5.91469  --min-latitude=50.2840167  --max-latitude=51.034368
 /// ```
-#[derive(Debug, Args)]
+#[derive(Debug, Clone, Args, Serialize)]
 pub struct GenerateInstance {
     /// An optional seed to kickstart the instance generation
     #[clap(short='s', long)]
@@ -27,83 +37,574 @@ pub struct GenerateInstance {
     /// The number of cities that must be visited
     #[clap(short='n', long, default_value="10")]
     pub nb_cities: usize,
+    /// If present, the name of a preset (urban-delivery, rural-service or intercity)
+    /// giving realistic values to the size of the area, the number and radius of
+    /// the clusters, the optional waypoints, the kind of travel costs and the
+    /// snapping. The area is centered on the --center, or on the middle of the box.
+    #[clap(long, value_parser=presets::parse, conflicts_with_all=["nb_centroids", "std_dev", "cluster_radius_km",
+        "optional_fraction", "duration", "force_routable", "max_snap_distance", "radius_km"])]
+    pub preset: Option<String>,
     /// The number of centroids that must be visited
     #[clap(short='c', long, default_value="3")]
     pub nb_centroids: usize,
     /// The std deviation between a city and its centroid
     #[clap(short='d', long, default_value="0.1")]
     pub std_dev: f32,
+    /// If present, the radius (in kilometres) around its centroid within which most
+    /// cities of a cluster lie (about 86% of them, that is two std deviations). This
+    /// replaces --std-dev, and accounts for the shrinking of the degrees of longitude
+    /// at the latitude of the box.
+    #[clap(long, conflicts_with="std_dev")]
+    pub cluster_radius_km: Option<f32>,
     /// The west most longitude allowed in this generation
-    #[clap(long, default_value="-4.4744")]
+    #[clap(long, default_value="-4.4744", value_parser=longitude)]
     pub min_longitude: f32,
     /// The east most longitude allowed in this generation
-    #[clap(long, default_value="8.1350")]
+    #[clap(long, default_value="8.1350", value_parser=longitude)]
     pub max_longitude: f32,
     /// The south most longitude allowed in this generation
-    #[clap(long, default_value="42.1958")]
+    #[clap(long, default_value="42.1958", value_parser=latitude)]
     pub min_latitude: f32,
     /// The north most longitude allowed in this generation
-    #[clap(long, default_value="51.0521")]
+    #[clap(long, default_value="51.0521", value_parser=latitude)]
     pub max_latitude: f32,
+    /// If present, the name of a place (e.g. "Ghent, Belgium") which is looked up
+    /// and around which the instance is generated. This replaces the bounding box
+    /// given by the min/max longitudes and latitudes.
+    #[clap(long, conflicts_with_all=["min_longitude", "max_longitude", "min_latitude", "max_latitude"])]
+    pub center: Option<String>,
+    /// The radius (in kilometres) of the area around the --center
+    #[clap(long, default_value="10")]
+    pub radius_km: f32,
+    /// URL of the geocoding service (Nominatim api) used to look up the --center
+    #[clap(long, default_value=geocoding::NOMINATIM)]
+    pub nominatim_url: String,
     /// Force all destinations to be routable (takes longer to generate an instance)
     #[clap(short, long)]
     pub force_routable: bool,
+    /// If present, the locations which would be moved farther than this distance
+    /// (in metres) when snapped to the nearest routable point are sampled anew
+    #[clap(long)]
+    pub max_snap_distance: Option<f32>,
     /// Base the distance matrix on duration rather than distance
     #[clap(short='D', long)]
     pub duration: bool,
 
-    /// Name of the file where to generate the tsp instance
+    /// Name of the file where to generate the tsp instance (`-` for the standard output)
     #[clap(short, long)]
     pub output: Option<String>,
+    /// Overwrite the output file if it already exists
+    #[clap(long)]
+    pub force: bool,
+    /// The fraction of the destinations (besides the depot) which are optional
+    /// waypoints that a tour may skip
+    #[clap(long, default_value="0.0")]
+    pub optional_fraction: f64,
+    /// The kind of tour sought for the generated instance. With gtsp, exactly one
+    /// destination of each cluster must be visited.
+    #[clap(long, value_enum, default_value_t=Variant::Tsp)]
+    pub variant: Variant,
+    /// Also write a small svg picture of the destinations next to each generated
+    /// instance (with the same name and the .svg extension)
+    #[clap(long)]
+    pub thumbnail: bool,
+    /// If present, the path where to export the instance in the GTSPLIB format
+    /// (only for a single instance of the gtsp variant)
+    #[clap(long)]
+    pub gtsplib: Option<String>,
+    /// If present, the travel cost matrix is made symmetric by replacing the costs of
+    /// both directions of each edge by their min, max or average
+    #[clap(long, value_enum)]
+    pub symmetrize: Option<Symmetrize>,
+    /// Enforce the triangle inequality by replacing each travel cost by that of the
+    /// shortest path (through other destinations) between the same endpoints
+    #[clap(long)]
+    pub metricize: bool,
+    /// If present, a nested family of instances having these numbers of cities is
+    /// generated (e.g. 10,20,50,100): each instance is a subset of the larger ones.
+    /// The instances are written next to the output with the size as suffix.
+    #[clap(long, value_delimiter=',')]
+    pub scale_series: Vec<usize>,
+    /// The number of instances which are generated (with consecutive seeds). The
+    /// instances are written next to the output with their number as suffix.
+    #[clap(long, default_value="1", conflicts_with="scale_series", requires="output")]
+    pub count: usize,
+    /// The maximum number of instances of a batch which are generated concurrently
+    /// (this bounds the number of concurrent requests sent to the osrm server)
+    #[clap(long, default_value="4")]
+    pub jobs: usize,
+    /// If present, the path of an instance whose centroids are reused (rather than
+    /// sampling new ones). The number of centroids is then that of this instance.
+    #[clap(long)]
+    pub centroids_from: Option<String>,
+
+    /// If present, the directory of the project whose conventionally named files
+    /// are used as default inputs and outputs of this command
+    #[clap(short='P', long)]
+    pub project: Option<String>,
 
     /// URL of the osrm server to use (optional)
     #[clap(short, long)]
     pub url_osrm: Option<String>,
+    /// If present, the routing profiles (e.g. car,bike) whose travel cost matrices
+    /// are all stored in the instance: the first one gives the distances, the other
+    /// ones are stored aside. The url of the osrm server must then comprise a
+    /// `{profile}` placeholder (e.g. https://routing.openstreetmap.de/routed-{profile})
+    #[clap(long, value_delimiter=',', requires="url_osrm")]
+    pub profiles: Vec<String>,
 }
 
 impl GenerateInstance {
     /// Executes this command
-    pub async fn execute(&self) {
-        let mut client = osrm_client::Client::default();
-        if let Some(url) = self.url_osrm.as_ref() {
-            client = client.base_url(url.clone());
+    pub async fn execute(&self, report: &mut Report) {
+        let mut generator = self.clone();
+        if let Some(name) = self.preset.as_ref() {
+            generator = generator.with_preset(&presets::preset(name));
+            report.json("preset", name);
+        }
+        if let Some(center) = generator.center.clone() {
+            generator = generator.centered(&center).await;
+            report.json("box", [generator.min_longitude, generator.min_latitude, generator.max_longitude, generator.max_latitude]);
+        }
+        generator.run(report).await
+    }
+
+    /// Returns a copy of this generator whose parameters are those of the given preset.
+    /// Without --center, its box shrinks to the area of the preset around the middle
+    /// of the box.
+    fn with_preset(&self, preset: &Preset) -> Self {
+        let generator = GenerateInstance {
+            radius_km: preset.radius_km,
+            nb_centroids: preset.nb_centroids,
+            cluster_radius_km: Some(preset.cluster_radius_km),
+            optional_fraction: preset.optional_fraction,
+            duration: preset.duration,
+            force_routable: preset.force_routable,
+            max_snap_distance: preset.max_snap_distance,
+            ..self.clone()
+        };
+        if generator.center.is_some() {
+            return generator;
+        }
+        let middle = Location {
+            longitude: (self.min_longitude + self.max_longitude) / 2.0,
+            latitude: (self.min_latitude + self.max_latitude) / 2.0,
+        };
+        let area = generator.around(middle);
+        GenerateInstance {
+            min_longitude: area.min_longitude.max(self.min_longitude),
+            max_longitude: area.max_longitude.min(self.max_longitude),
+            min_latitude: area.min_latitude.max(self.min_latitude),
+            max_latitude: area.max_latitude.min(self.max_latitude),
+            ..area
+        }
+    }
+
+    /// Returns a copy of this generator whose bounding box is the square of side
+    /// 2 * radius centered on the location of the given place
+    async fn centered(&self, center: &str) -> Self {
+        if self.radius_km.is_nan() || self.radius_km <= 0.0 {
+            clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!(
+                "--radius-km ({}) must be positive\n", self.radius_km)).exit();
+        }
+        let location = geocoding::geocode(&self.nominatim_url, center).await
+            .unwrap_or_else(|message| clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!("{message}\n")).exit());
+        self.around(location)
+    }
+
+    /// Returns a copy of this generator whose bounding box is the square of side
+    /// 2 * radius centered on the given location
+    fn around(&self, Location{longitude, latitude}: Location) -> Self {
+        let d_lat = self.radius_km / KM_PER_DEGREE;
+        let d_lon = d_lat / sampling::cos(latitude.to_radians() as f64) as f32;
+        GenerateInstance {
+            center: None,
+            min_longitude: longitude - d_lon,
+            max_longitude: longitude + d_lon,
+            min_latitude: (latitude - d_lat).max(-90.0),
+            max_latitude: (latitude + d_lat).min(90.0),
+            ..self.clone()
+        }
+    }
+
+    /// Generates the instance(s) within the bounding box of this generator
+    async fn run(&self, report: &mut Report) {
+        if let Err(message) = self.validate() {
+            clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!("{message}\n")).exit();
         }
+        let client = self.client(self.profiles.first().map(String::as_str));
 
-        let instance  = self.generate(&client).await;
-        let instance = serde_json::to_string_pretty(&instance).unwrap();
+        let project = self.project.as_deref().map(Project::open);
+        let output = self.output.clone().or_else(|| project.as_ref().map(Project::instance));
+        self.check_outputs(output.as_deref());
 
-        if let Some(output) = self.output.as_ref() {
-            File::create(output).unwrap().write_all(instance.as_bytes()).unwrap();
+        if self.count > 1 {
+            let output = output.as_deref().expect("a batch of instances needs an --output");
+            let seed = self.base_seed();
+            let batch = self.generate_batch(&client, output, seed, report.format() == Format::Human).await;
+            let skipped = batch.iter().filter(|(_, skip)| *skip).count();
+            let outputs = batch.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>();
+            report.json("seed", seed.to_string());
+            report.json("skipped", skipped);
+            let line = report.messages().format("batch-outputs", &[("generated", &(outputs.len() - skipped)), ("skipped", &skipped)]);
+            report.field("outputs", &outputs, line);
+            if output != files::STDIO {
+                // the instances which were skipped are described by the manifest of the previous run
+                let manifest = Manifest::path(output);
+                let previous = (skipped > 0 && Path::new(&manifest).is_file()).then(|| files::read_manifest(&manifest));
+                let entries = batch.iter().enumerate()
+                    .map(|(k, (path, skip))| {
+                        let seed = seed.wrapping_add(k as u128);
+                        previous.as_ref()
+                            .filter(|_| *skip)
+                            .and_then(|m| m.entry(path, seed))
+                            .unwrap_or_else(|| Entry::new(path, seed, &files::read_instance(path)))
+                    })
+                    .collect();
+                let manifest = Manifest { parameters: serde_json::to_value(self).unwrap(), instances: entries };
+                report.json("manifest", manifest.write(output));
+            }
+        } else if self.scale_series.is_empty() {
+            let instance  = self.generate(&client).await;
+            if let Some(stats) = instance.metadata.snapping {
+                let (min, mean, max) = (format!("{:.0}", stats.min), format!("{:.0}", stats.mean), format!("{:.0}", stats.max));
+                let line = report.messages().format("snapping",
+                    &[("min", &min), ("mean", &mean), ("max", &max), ("resampled", &stats.resampled)]);
+                report.field("snapping", stats, line);
+            }
+            let completed = &instance.metadata.completed;
+            if !completed.is_empty() {
+                let line = report.messages().format("completed", &[("value", &completed.len())]);
+                report.field("completed", completed, line);
+            }
+            if let Some(changed) = instance.metadata.metricized {
+                let line = report.messages().format("metricized", &[("value", &changed)]);
+                report.field("metricized_cells", changed, line);
+            }
+            if !instance.metadata.postprocessing.is_empty() {
+                report.json("postprocessing", &instance.metadata.postprocessing);
+            }
+            if let Some(path) = self.gtsplib.as_ref() {
+                let name = Path::new(path).file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+                files::write_text(path, &instance.gtsplib_text(&name), self.force);
+                report.json("gtsplib", path);
+            }
+            let text = serde_json::to_string_pretty(&instance).unwrap();
+            if let Some(output) = output.as_ref() {
+                files::write_text(output, &text, self.force);
+                self.write_thumbnail(output, &instance);
+                report.json("output", output);
+            } else {
+                report.field("instance", &instance, text);
+            }
         } else {
-            println!("{instance}");
+            let seed = self.base_seed();
+            let series = GenerateInstance { seed: Some(seed), ..self.clone() }.generate_series(&client).await;
+            let mut outputs = vec![];
+            for instance in series.iter() {
+                let text = serde_json::to_string_pretty(instance).unwrap();
+                if let Some(output) = output.as_ref() {
+                    let output = Self::numbered_path(output, instance.len());
+                    files::write_text(&output, &text, self.force);
+                    self.write_thumbnail(&output, instance);
+                    outputs.push(output);
+                } else {
+                    report.line(text);
+                }
+            }
+            if let Some(output) = output.as_ref().filter(|o| *o != files::STDIO) {
+                let entries = outputs.iter().zip(series.iter())
+                    .map(|(path, instance)| Entry::new(path, seed, instance))
+                    .collect();
+                let manifest = Manifest { parameters: serde_json::to_value(self).unwrap(), instances: entries };
+                report.json("manifest", manifest.write(output));
+            }
+            if output.is_some() {
+                report.json("outputs", outputs);
+            } else {
+                report.json("instances", series);
+            }
+        }
+        if let Some(project) = project {
+            project.log_command("generate", report.document());
+        }
+    }
+
+    /// This method checks that the combination of the options makes sense, so that
+    /// nonsensical settings are reported before anything gets generated
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_longitude >= self.max_longitude {
+            return Err(format!("--min-longitude ({}) must be smaller than --max-longitude ({})",
+                self.min_longitude, self.max_longitude));
+        }
+        if self.min_latitude >= self.max_latitude {
+            return Err(format!("--min-latitude ({}) must be smaller than --max-latitude ({})",
+                self.min_latitude, self.max_latitude));
+        }
+        match self.cluster_radius_km {
+            Some(radius) if radius.is_nan() || radius <= 0.0 =>
+                return Err(format!("--cluster-radius-km ({radius}) must be positive")),
+            None if self.std_dev.is_nan() || self.std_dev <= 0.0 =>
+                return Err(format!("--std-dev ({}) must be positive", self.std_dev)),
+            _ => {},
+        }
+        let (sigma_lon, sigma_lat) = self.std_devs();
+        let width  = self.max_longitude - self.min_longitude;
+        let height = self.max_latitude - self.min_latitude;
+        let spread = (width / sigma_lon).max(height / sigma_lat);
+        if spread > MAX_SPREAD {
+            let hint = if self.preset.is_some() { "use a smaller box" } else { "use a larger --std-dev or --cluster-radius-km, or a smaller box" };
+            return Err(format!("the box ({width} x {height} degrees) is {spread:.0} times as large as the spread \
+                of the clusters (at most {MAX_SPREAD}): {hint}"));
+        }
+        if self.centroids_from.is_none() {
+            if self.nb_centroids == 0 {
+                return Err("--nb-centroids must be at least 1".to_string());
+            }
+            if self.nb_centroids > self.nb_cities {
+                return Err(format!("--nb-centroids ({}) cannot exceed --nb-cities ({}): some clusters would be empty",
+                    self.nb_centroids, self.nb_cities));
+            }
+        }
+        if !self.profiles.is_empty() && !self.url_osrm.as_ref().is_some_and(|url| url.contains("{profile}")) {
+            return Err("--profiles needs an --url-osrm comprising a {profile} placeholder".to_string());
+        }
+        if self.gtsplib.is_some() && self.variant != Variant::Gtsp {
+            return Err("--gtsplib is only meant for the gtsp variant (use --variant gtsp)".to_string());
+        }
+        if self.gtsplib.is_some() && (self.count > 1 || !self.scale_series.is_empty()) {
+            return Err("--gtsplib exports a single instance: it cannot be combined with --count or --scale-series".to_string());
         }
+        if !(0.0..=1.0).contains(&self.optional_fraction) {
+            return Err(format!("--optional-fraction ({}) must lie between 0 and 1", self.optional_fraction));
+        }
+        Ok(())
     }
 
     /// This is the method you want to call in order to generate a clustered TSP instance
     pub async fn generate(&self, client: &Client) -> Instance {
         let mut rng = self.rng();
-        let centroids = self.generate_centroids(&mut rng);
-        let centroids = self.routable_cities(client, &centroids).await;
-        let mut destinations = self.generate_cities(&mut rng, &centroids);
+        let mut resampled_centroids = 0;
+        let centroids = if let Some(path) = self.centroids_from.as_ref() {
+            Self::load_centroids(path)
+        } else {
+            let centroids = self.generate_centroids(&mut rng);
+            let (centroids, _, resampled) = self.routable_cities(client, &centroids, |_| self.random_centroid(&mut rng)).await
+                .unwrap_or_else(|k| self.snap_failure(format!("the centroid of cluster {k}")));
+            resampled_centroids = resampled;
+            centroids
+        };
+        let (mut destinations, clusters) = self.generate_cities(&mut rng, &centroids);
+        let mut snap_distances = vec![];
+        let mut snapping = None;
         if self.force_routable {
-            destinations = self.routable_cities(client, &destinations).await;
+            let resample = |i: usize| self.random_pos_close_to(&mut rng, centroids[clusters[i]]);
+            let (snapped, distances, resampled) = self.routable_cities(client, &destinations, resample).await
+                .unwrap_or_else(|i| {
+                    let Location{longitude, latitude} = centroids[clusters[i]];
+                    self.snap_failure(format!("destination {i} of cluster {} (centroid at longitude {longitude:.5}, latitude {latitude:.5})", clusters[i]))
+                });
+            destinations = snapped;
+            snap_distances = distances;
+            snapping = SnapStats::new(&snap_distances, resampled_centroids + resampled);
         }
 
-        let distances = self.travel_cost_matrix(client, &destinations).await;
+        let optional = self.generate_optional(&mut rng, destinations.len());
+        let mut distances = Self::travel_cost_matrix(client, &destinations, self.duration).await;
+        let (completed, metricized) = self.postprocess(&mut distances);
+        let mut profiles = BTreeMap::new();
+        let mut postprocessing = BTreeMap::new();
+        for profile in self.profiles.iter().skip(1) {
+            let mut matrix = Self::travel_cost_matrix(&self.client(Some(profile)), &destinations, self.duration).await;
+            let (completed, metricized) = self.postprocess(&mut matrix);
+            profiles.insert(profile.clone(), matrix);
+            postprocessing.insert(profile.clone(), Postprocessing { metricized, completed });
+        }
 
         Instance{
             destinations,
-            distances
+            distances,
+            profiles,
+            clusters,
+            metadata: Metadata { centroids, durations: self.duration, symmetrized: self.symmetrize, metricized, completed, snap_distances, snapping,
+                profiles: self.profiles.clone(), postprocessing },
+            optional,
+            variant: self.variant,
+            ..Instance::default()
         }
     }
+
+    /// This method returns the client of the osrm server serving the given routing
+    /// profile (or the default one)
+    fn client(&self, profile: Option<&str>) -> Client {
+        let mut client = Client::default();
+        if let Some(url) = self.url_osrm.as_ref() {
+            client = client.base_url(profile.map_or(url.clone(), |p| url.replace("{profile}", p)));
+        }
+        client
+    }
+
+    /// This method completes, symmetrizes and metricizes the given travel cost
+    /// matrix as requested. It returns the completed pairs and the number of costs
+    /// lowered to enforce the triangle inequality (if that was requested). The
+    /// process terminates with an error when some destination cannot be reached.
+    fn postprocess(&self, matrix: &mut [Vec<f32>]) -> (Vec<(usize, usize)>, Option<usize>) {
+        let completed = postprocess::complete(matrix)
+            .unwrap_or_else(|message| clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!(
+                "{message}: osrm finds no route to it (use --force-routable or another --seed)\n"))
+                .exit());
+        if let Some(rule) = self.symmetrize {
+            postprocess::symmetrize(matrix, rule);
+        }
+        let metricized = self.metricize.then(|| postprocess::metricize(matrix));
+        (completed, metricized)
+    }
+
+    /// This method returns the centroids stored in the metadata of the given instance
+    fn load_centroids(path: &str) -> Vec<Location> {
+        let centroids = files::read_instance(path).metadata.centroids;
+        if centroids.is_empty() {
+            clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!(
+                "{path}: the instance does not record its centroids, they cannot be reused (--centroids-from)\n"))
+                .exit();
+        }
+        centroids
+    }
     
-    /// This method returns an initialized random number generator
+    /// This method generates a nested family of instances (one per size of the scale
+    /// series), all of which are subsets of the largest one. The destinations of
+    /// the largest instance are ordered by picking one city from each cluster in
+    /// turn, so that the first n destinations are spread over the clusters just as
+    /// they would be in an instance of n cities.
+    pub async fn generate_series(&self, client: &Client) -> Vec<Instance> {
+        let largest = self.scale_series.iter().copied().max().unwrap_or(self.nb_cities);
+        let generator = GenerateInstance { nb_cities: largest, ..self.clone() };
+        let instance = generator.generate(client).await;
+
+        let mut members = vec![vec![]; instance.metadata.centroids.len()];
+        for (i, c) in instance.clusters.iter().copied().enumerate() {
+            members[c].push(i);
+        }
+        let mut order = vec![];
+        for rank in 0..largest {
+            order.extend(members.iter().filter_map(|m| m.get(rank)));
+        }
+        let instance = instance.subset(&order);
+
+        let mut sizes = self.scale_series.clone();
+        sizes.sort_unstable();
+        sizes.dedup();
+        sizes.iter()
+            .map(|n| instance.subset(&(0..*n).collect::<Vec<_>>()))
+            .collect()
+    }
+
+    /// This method generates a batch of instances, the k-th of which uses the seed
+    /// `seed + k` and is named after it. At most `jobs` instances are generated
+    /// concurrently, and the progress is displayed on the standard error if
+    /// `progress` is set. It returns the paths of the instances (in the order of
+    /// their seeds), each one along with a flag telling whether it was skipped.
+    ///
+    /// Unless --force is given, the instances whose file exists already are not
+    /// generated again, so that an interrupted batch resumes where it left off
+    /// when it is run again with the same seed. (The files are written atomically,
+    /// hence the existing ones are always complete.)
+    async fn generate_batch(&self, client: &Client, output: &str, seed: u128, progress: bool) -> Vec<(String, bool)> {
+        let mut done = 0;
+        let mut outputs = futures::stream::iter(0..self.count)
+            .map(|k| async move {
+                let seed = seed.wrapping_add(k as u128);
+                let path = Self::numbered_path(output, seed);
+                if !self.force && Path::new(&path).exists() {
+                    return (k, path, true);
+                }
+                let generator = GenerateInstance { seed: Some(seed), count: 1, ..self.clone() };
+                let instance = generator.generate(client).await;
+                files::write_text(&path, &serde_json::to_string_pretty(&instance).unwrap(), self.force);
+                self.write_thumbnail(&path, &instance);
+                (k, path, false)
+            })
+            .buffer_unordered(self.jobs.max(1))
+            .inspect(|_| {
+                done += 1;
+                if progress {
+                    eprint!("\rgenerated {done}/{} instances", self.count);
+                }
+            })
+            .collect::<Vec<_>>()
+            .await;
+        if progress {
+            eprintln!();
+        }
+        outputs.sort_unstable();
+        outputs.into_iter().map(|(_, path, skip)| (path, skip)).collect()
+    }
+
+    /// Terminates with an error before anything gets generated if one of the files
+    /// to write exists already (unless --force is given). The existing instances of
+    /// a batch are not overwritten but skipped, hence they are not checked.
+    fn check_outputs(&self, output: Option<&str>) {
+        if self.count > 1 {
+            return;
+        }
+        let mut paths = vec![];
+        if let Some(output) = output {
+            if self.scale_series.is_empty() {
+                paths.push(output.to_string());
+            } else {
+                paths.extend(self.scale_series.iter().map(|n| Self::numbered_path(output, n)));
+            }
+        }
+        if self.thumbnail {
+            let thumbnails = paths.iter()
+                .filter(|p| *p != files::STDIO)
+                .map(|p| Path::new(p).with_extension("svg").to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            paths.extend(thumbnails);
+        }
+        if self.scale_series.is_empty() {
+            paths.extend(self.gtsplib.clone());
+        }
+        paths.iter().for_each(|path| files::check_output(path, self.force));
+    }
+
+    /// Writes the thumbnail of the given instance next to the file where it has been
+    /// written (if thumbnails were requested)
+    fn write_thumbnail(&self, output: &str, instance: &Instance) {
+        if self.thumbnail && output != files::STDIO {
+            let path = Path::new(output).with_extension("svg").to_string_lossy().into_owned();
+            files::write_text(&path, &visualisation::thumbnail(instance, Palette::default()), self.force);
+        }
+    }
+
+    /// Returns the path where the instance having the given number (its size in a
+    /// series, its seed in a batch) is written
+    fn numbered_path(output: &str, number: impl Display) -> String {
+        if output == files::STDIO {
+            return output.to_string();
+        }
+        let path = Path::new(output);
+        let stem = path.file_stem().map(|s| s.to_string_lossy()).unwrap_or_default();
+        let name = match path.extension() {
+            Some(ext) => format!("{stem}-{number}.{}", ext.to_string_lossy()),
+            None => format!("{stem}-{number}"),
+        };
+        path.with_file_name(name).to_string_lossy().into_owned()
+    }
+
+    /// This method returns the seed of the generation (the current time if none was given)
+    fn base_seed(&self) -> u128 {
+        self.seed.unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis())
+    }
+
+    /// This method returns an initialized random number generator. The algorithm is
+    /// pinned to ChaCha20 so that a given seed yields the same instance everywhere.
     fn rng(&self) -> impl Rng {
-        let init = self.seed.unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis());
+        let init = self.base_seed();
         let mut seed = [0_u8; 32];
-        seed.iter_mut().zip(init.to_be_bytes().into_iter()).for_each(|(s, i)| *s = i);
-        seed.iter_mut().rev().zip(init.to_le_bytes().into_iter()).for_each(|(s, i)| *s = i);
-        ChaChaRng::from_seed(seed)
+        seed.iter_mut().zip(init.to_be_bytes()).for_each(|(s, i)| *s = i);
+        seed.iter_mut().rev().zip(init.to_le_bytes()).for_each(|(s, i)| *s = i);
+        ChaCha20Rng::from_seed(seed)
     }
 
     /// This method returns a vector of random centroids for this instance
@@ -117,81 +618,196 @@ impl GenerateInstance {
 
     /// This method returns a new random centroid uniformly sampled from 0..max
     fn random_centroid(&self, rng: &mut impl Rng) -> Location {
-        //
-        let lon_dist = Uniform::new_inclusive(self.min_longitude, self.max_longitude);
-        let lat_dist = Uniform::new_inclusive(self.min_latitude, self.max_latitude);
-        let longitude = lon_dist.sample(rng);
-        let latitude = lat_dist.sample(rng);
+        let longitude = sampling::uniform(rng, self.min_longitude, self.max_longitude);
+        let latitude = sampling::uniform(rng, self.min_latitude, self.max_latitude);
         Location { longitude, latitude }
     }
 
-    /// This method returns a vector of random cities close to the centroids
-    fn generate_cities(&self, rng: &mut impl Rng, centroids: &[Location]) -> Vec<Location> {
-        let mut cities_per_centroids = vec![self.nb_cities / self.nb_centroids; self.nb_centroids];
-        for i in 0..(self.nb_cities % self.nb_centroids) {
-            cities_per_centroids[i] += 1;
-        }
+    /// This method returns a vector of random cities close to the centroids, along with
+    /// the index of the centroid each city has been sampled around
+    fn generate_cities(&self, rng: &mut impl Rng, centroids: &[Location]) -> (Vec<Location>, Vec<usize>) {
+        let nb_centroids = centroids.len();
+        let mut cities_per_centroids = vec![self.nb_cities / nb_centroids; nb_centroids];
+        cities_per_centroids.iter_mut()
+            .take(self.nb_cities % nb_centroids)
+            .for_each(|c| *c += 1);
 
         let mut cities = vec![];
+        let mut clusters = vec![];
         for (i, centroid) in centroids.iter().copied().enumerate() {
             let n = cities_per_centroids[i];
             for _ in 0..n {
                 cities.push(self.random_pos_close_to(rng, centroid));
+                clusters.push(i);
+            }
+        }
+        (cities, clusters)
+    }
+
+    /// This method decides which destinations are optional waypoints. The random
+    /// draws only happen when some destinations are to be optional, so that the
+    /// instances without optional waypoints do not depend on this feature.
+    fn generate_optional(&self, rng: &mut impl Rng, n: usize) -> Vec<bool> {
+        if self.optional_fraction <= 0.0 {
+            return vec![];
+        }
+        (0..n).map(|i| i != 0 && sampling::unit(rng) < self.optional_fraction).collect()
+    }
+
+    /// This method returns the std deviations (in degrees of longitude and latitude)
+    /// of the cities around their centroid
+    fn std_devs(&self) -> (f32, f32) {
+        match self.cluster_radius_km {
+            None => (self.std_dev, self.std_dev),
+            Some(radius) => {
+                let sigma = radius / 2.0 / KM_PER_DEGREE;
+                let latitude = (self.min_latitude + self.max_latitude) / 2.0;
+                (sigma / sampling::cos(latitude.to_radians() as f64) as f32, sigma)
             }
         }
-        cities
     }
 
     /// This method returns a new city close to the given centroid
     fn random_pos_close_to(&self, rng: &mut impl Rng, Location{longitude, latitude}: Location) -> Location {
-        let dist_x = Normal::new(longitude, self.std_dev).expect("cannot create normal dist");
-        let dist_y = Normal::new(latitude,  self.std_dev).expect("cannot create normal dist");
-        let lon = dist_x.sample(rng);
-        let lat = dist_y.sample(rng);
-        Location { longitude: lon, latitude: lat }
+        if self.cluster_radius_km.is_none() {
+            let (lon, lat) = sampling::normal_pair(rng, (longitude, latitude), self.std_dev);
+            return Location { longitude: lon, latitude: lat };
+        }
+        let (sigma_lon, sigma_lat) = self.std_devs();
+        let (u, v) = sampling::normal_pair(rng, (0.0, 0.0), 1.0);
+        Location { longitude: longitude + sigma_lon * u, latitude: latitude + sigma_lat * v }
     }
     
     /// This method maps a set of location to the nearset routable point on the map.
-    async fn routable_cities(&self, client: &Client, locations: &[Location]) -> Vec<Location> {
+    /// The locations which would be moved farther than the max snap distance are
+    /// replaced by `resample(i)` (i being their index) until they lie close enough
+    /// to a road. It also returns the distance by which each location was moved, and
+    /// the number of resampled locations. The error is the index of a location which
+    /// could not be snapped close enough to a road within `MAX_SNAP_ATTEMPTS`.
+    async fn routable_cities(&self, client: &Client, locations: &[Location], mut resample: impl FnMut(usize) -> Location)
+        -> Result<(Vec<Location>, Vec<f32>, usize), usize>
+    {
         let mut out = vec![];
-        for loc in locations {
-            let rsp = NearestRequestBuilder::default()
-                .coordinates(osrm_client::Coordinates::Single(*loc))
-                .build()
-                .unwrap()
-                .send(&client)
-                .await
-                .unwrap();
-            
-            let wp = &rsp.waypoints.unwrap()[0];
-            out.push(wp.location);
+        let mut moves = vec![];
+        let mut resampled = 0;
+        for (i, mut loc) in locations.iter().copied().enumerate() {
+            let mut snapped = Self::nearest(client, loc).await;
+            let mut attempts = 1;
+            while self.max_snap_distance.is_some_and(|max| haversine(loc, snapped) > max) {
+                if attempts >= MAX_SNAP_ATTEMPTS {
+                    return Err(i);
+                }
+                loc = resample(i);
+                resampled += 1;
+                snapped = Self::nearest(client, loc).await;
+                attempts += 1;
+            }
+            moves.push(haversine(loc, snapped));
+            out.push(snapped);
         }
-        out
+        Ok((out, moves, resampled))
+    }
+
+    /// Exits with an error telling that the given location could not be snapped
+    /// close enough to a road
+    fn snap_failure(&self, what: String) -> ! {
+        clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!(
+            "{what} could not be snapped within {} m of a road after {MAX_SNAP_ATTEMPTS} attempts \
+            (use a larger --max-snap-distance or a box covering more land)\n", self.max_snap_distance.unwrap_or_default()))
+            .exit()
+    }
+
+    /// This method returns the routable point which is nearest to the given location
+    pub async fn nearest(client: &Client, location: Location) -> Location {
+        let rsp = NearestRequestBuilder::default()
+            .coordinates(osrm_client::Coordinates::Single(location))
+            .build()
+            .unwrap()
+            .send(client)
+            .await
+            .unwrap();
+
+        rsp.waypoints.unwrap()[0].location
     }
 
     /// This method computes the travel cost matrix between all the given locations. Depending
     /// on the 'duration' flag, this method will either return a matrix of durations (in seconds) 
     /// to reach each location from each other; or it will return the actual distance that is going 
-    /// to be travelled (in metres).
-    async fn travel_cost_matrix(&self, client: &Client, locations: &[Location]) -> Vec<Vec<f32>>{
+    /// to be travelled (in metres). The pairs which cannot be routed get an infinite cost.
+    pub async fn travel_cost_matrix(client: &Client, locations: &[Location], duration: bool) -> Vec<Vec<f32>>{
         let matrix = TableRequestBuilder::default()
             .coordinates(osrm_client::Coordinates::Multi(Vec::from_iter(locations.iter().copied())))
             .annotations(TableAnnotationRequest::Both)
             .build().unwrap()
-            .send(&client)
+            .send(client)
             .await
             .unwrap();
 
         let mut result = vec![];
-        if self.duration {
+        if duration {
             for line in matrix.durations.unwrap().iter() {
-                result.push(line.iter().map(|x| x.unwrap()).collect());
+                result.push(line.iter().map(|x| x.unwrap_or(f32::INFINITY)).collect());
             }
         } else {
             for line in matrix.distances.unwrap().iter() {
-                result.push(line.iter().map(|x| x.unwrap()).collect());
+                result.push(line.iter().map(|x| x.unwrap_or(f32::INFINITY)).collect());
             }
         }
         result
     }
 }
+
+/// Parses a longitude, which must lie between -180 and 180 degrees
+fn longitude(text: &str) -> Result<f32, String> {
+    coordinate(text, 180.0)
+}
+
+/// Parses a latitude, which must lie between -90 and 90 degrees
+fn latitude(text: &str) -> Result<f32, String> {
+    coordinate(text, 90.0)
+}
+
+/// Parses a coordinate (in degrees) whose absolute value is at most `bound`
+fn coordinate(text: &str, bound: f32) -> Result<f32, String> {
+    let value = text.parse::<f32>().map_err(|e| format!("{text} is not a number: {e}"))?;
+    if !(-bound..=bound).contains(&value) {
+        return Err(format!("{value} is not between -{bound} and {bound} degrees"));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::FromArgMatches;
+
+    use super::*;
+
+    /// Returns the generator configured by the given command line arguments
+    fn generator(args: &[&str]) -> GenerateInstance {
+        let command = GenerateInstance::augment_args(clap::Command::new("generate"));
+        let matches = command.get_matches_from(std::iter::once("generate").chain(args.iter().copied()));
+        GenerateInstance::from_arg_matches(&matches).unwrap()
+    }
+
+    #[test]
+    fn presets_are_valid_in_the_default_box() {
+        for (name, preset) in presets::presets() {
+            let generator = generator(&["--preset", &name]).with_preset(&preset);
+            assert_eq!(generator.validate(), Ok(()), "the preset {name} is invalid");
+        }
+    }
+
+    /// The cities sampled for a given seed must be the same on every platform: should
+    /// this fixture ever need to be updated, the instances of a seed have changed
+    #[test]
+    fn generated_cities_are_pinned() {
+        let generator = generator(&["--seed", "2024", "--nb-cities", "5", "--nb-centroids", "2"]);
+        let mut rng = generator.rng();
+        let centroids = generator.generate_centroids(&mut rng);
+        let (cities, clusters) = generator.generate_cities(&mut rng, &centroids);
+        let bits = cities.iter().map(|c| (c.longitude.to_bits(), c.latitude.to_bits())).collect::<Vec<_>>();
+        assert_eq!(clusters, [0, 0, 0, 1, 1]);
+        assert_eq!(bits, [(3217091241, 1111487982), (3216438211, 1111425574), (3215853760, 1111485440),
+            (1069525348, 1110533623), (1070467741, 1110559755)]);
+    }
+}
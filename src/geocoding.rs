@@ -0,0 +1,35 @@
+//! This module looks up the location of a place from its name with a geocoding
+//! service which implements the Nominatim api (`GET {url}/search`).
+
+use osrm_client::Location;
+use serde::Deserialize;
+
+/// The public Nominatim service of OpenStreetMap (its usage policy asks for at most
+/// one request per second)
+pub const NOMINATIM: &str = "https://nominatim.openstreetmap.org";
+
+#[derive(Debug, Deserialize)]
+struct Place {
+    lat: String,
+    lon: String,
+}
+
+/// Returns the location of the place which best matches the given name, or the
+/// description of the reason why it could not be found
+pub async fn geocode(url: &str, name: &str) -> Result<Location, String> {
+    let places: Vec<Place> = reqwest::Client::new()
+        .get(format!("{url}/search"))
+        .query(&[("q", name), ("format", "jsonv2"), ("limit", "1")])
+        .header(reqwest::header::USER_AGENT, concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+        .send().await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("cannot reach the geocoding service {url} (use --nominatim-url to pick another one): {e}"))?
+        .json().await
+        .map_err(|e| format!("the geocoding service {url} returned an invalid answer: {e}"))?;
+    let place = places.first()
+        .ok_or_else(|| format!("no place named {name:?} could be found (check its spelling, or add the region or country)"))?;
+    match (place.lon.parse(), place.lat.parse()) {
+        (Ok(longitude), Ok(latitude)) => Ok(Location { longitude, latitude }),
+        _ => Err(format!("the geocoding service {url} returned invalid coordinates ({}, {}) for {name:?}", place.lon, place.lat)),
+    }
+}
@@ -0,0 +1,64 @@
+## results of the commands
+is-exact = is exact { $value }
+best-value = best value { $value }
+valid = valid { $value }
+value = value { $value }
+violation = violation: { $value }
+working-hours = working hours { $value }
+fits-shift = fits shift { $value }
+nb-days = { $value } days
+day-summary = day { $day }: { $stops } ({ $hours } h)
+split-needed = split needed before destination { $destination } (position { $position })
+overlong-leg = the leg { $from } -> { $to } (position { $position }) does not fit in any shift
+selftest-case-ok = { $name } ok
+selftest-case-failed = { $name } FAILED
+selftest-passed = selftest passed
+selftest-failed = selftest failed
+batch-outputs = { $generated } instances generated, { $skipped } already present
+snapping = snap displacement min { $min } m mean { $mean } m max { $max } m, { $resampled } points resampled
+completed = { $value } unroutable pairs completed through other destinations
+metricized = { $value } costs lowered to enforce the triangle inequality
+cost-value = cost { $value }
+co2-value = co2 { $value } kg
+yes = true
+no = false
+wall-time = wall time { $value }s
+cpu-time = cpu time { $value }s
+peak-rss = peak rss { $value } kB
+explored-nodes = explored nodes { $value }
+restricted-dds = restricted dds { $value }
+relaxed-dds = relaxed dds { $value }
+phase = phase { $phase } width { $width } best value { $value } exact { $exact } wall time { $time }s explored nodes { $explored }
+none = none
+solution = solution: { $value }
+lower-bound = lower bound { $value }
+gap = gap { $value }%
+initial-value = initial value { $value }
+overrides = overrides { $value }
+required = required { $value }
+forbidden = forbidden { $value }
+fit-summary = { $stops } stops, { $clusters } clusters, std deviation { $sigma } degrees, { $outliers } outliers
+compare-instance = instance
+compare-reference = reference
+compare-stops = stops
+compare-mean-nearest = mean nearest (m)
+compare-mean-pairwise = mean pairwise (m)
+compare-largest-cluster = largest cluster
+divergence = divergence: nearest neighbours KS { $nearest }, pairwise distances JS { $pairwise }, cluster sizes TV { $clusters }
+
+## labels of the maps
+distance-unit = km
+duration = { $hours } hours { $minutes } minutes { $seconds } seconds
+cost = cost
+co2 = kg CO₂
+day = day
+destination = destination
+elevation = elevation
+legend = legend
+status-unchanged = unchanged
+status-moved = moved
+status-added = added
+status-removed = removed
+stop = stop
+map-description = Map of the destinations. Press n or p to move to the next or previous stop.
+profile-description = Elevation profile along the route
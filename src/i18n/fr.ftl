@@ -0,0 +1,86 @@
+## results of the commands
+is-exact = exact { $value }
+best-value = meilleure valeur { $value }
+valid = valide { $value }
+value = valeur { $value }
+violation = violation : { $value }
+working-hours = heures de travail { $value }
+fits-shift = tient dans le service { $value }
+nb-days = { $value } jours
+day-summary = jour { $day } : { $stops } ({ $hours } h)
+split-needed = interruption nécessaire avant la destination { $destination } (position { $position })
+overlong-leg = le trajet { $from } -> { $to } (position { $position }) ne tient dans aucun service
+selftest-case-ok = { $name } ok
+selftest-case-failed = { $name } ÉCHEC
+selftest-passed = autotest réussi
+selftest-failed = autotest échoué
+batch-outputs = { $generated } instances générées, { $skipped } déjà présentes
+snapping = déplacement vers la route min { $min } m moyen { $mean } m max { $max } m, { $resampled } points tirés à nouveau
+completed = { $value } paires non routables complétées via d'autres destinations
+metricized = { $value } coûts abaissés pour respecter l'inégalité triangulaire
+cost-value = coût { $value }
+co2-value = CO₂ { $value } kg
+yes = oui
+no = non
+wall-time = temps écoulé { $value } s
+cpu-time = temps cpu { $value } s
+peak-rss = mémoire maximale { $value } kB
+explored-nodes = nœuds explorés { $value }
+restricted-dds = diagrammes restreints { $value }
+relaxed-dds = diagrammes relâchés { $value }
+phase = phase { $phase } largeur { $width } meilleure valeur { $value } exact { $exact } temps écoulé { $time } s nœuds explorés { $explored }
+none = aucune
+solution = solution : { $value }
+lower-bound = borne inférieure { $value }
+gap = écart { $value } %
+initial-value = valeur initiale { $value }
+overrides = coûts personnalisés { $value }
+required = arcs imposés { $value }
+forbidden = arcs interdits { $value }
+fit-summary = { $stops } arrêts, { $clusters } clusters, écart type { $sigma } degrés, { $outliers } points aberrants
+compare-instance = instance
+compare-reference = référence
+compare-stops = arrêts
+compare-mean-nearest = plus proche moyen (m)
+compare-mean-pairwise = distance moyenne (m)
+compare-largest-cluster = plus grand cluster
+divergence = divergence : plus proches voisins KS { $nearest }, distances deux à deux JS { $pairwise }, tailles des clusters TV { $clusters }
+
+## labels of the maps
+distance-unit = km
+duration = { $hours } heures { $minutes } minutes { $seconds } secondes
+cost = coût
+co2 = kg de CO₂
+day = jour
+destination = destination
+elevation = altitude
+legend = légende
+status-unchanged = inchangée
+status-moved = déplacée
+status-added = ajoutée
+status-removed = supprimée
+stop = arrêt
+map-description = Carte des destinations. Appuyez sur n ou p pour passer à l'arrêt suivant ou précédent.
+profile-description = Profil d'altitude le long de l'itinéraire
+
+## help of the command line
+help-about = Génère des instances de TSP dont les destinations sont groupées en clusters
+help-json = Affiche les résultats de la commande sous la forme d'un unique document json
+help-quiet = N'affiche rien (hormis les erreurs)
+help-porcelain = Affiche les résultats sous la forme de lignes `clé valeur` stables séparées par des tabulations
+help-lang = La langue des textes destinés aux humains (résultats, cartes et aide)
+help-generate = Génère des instances de TSP réalistes dont les villes à visiter sont groupées en clusters
+help-visualize = Génère un fichier html pour visualiser une instance et une éventuelle solution
+help-solve = Résout une instance par séparation et évaluation avec des diagrammes de décision
+help-bound = Calcule une borne inférieure certifiée de la longueur du tour optimal et la rapporte avec un tour heuristique, afin de donner l'écart d'optimalité de ce tour sans résoudre l'instance de manière exacte
+help-polish = Améliore un tour existant (produit p. ex. par un autre outil) par une recherche locale limitée dans le temps, et écrit le tour amélioré
+help-check = Vérifie qu'une solution visite chaque destination obligatoire d'une instance exactement une fois (et les optionnelles au plus une fois) et rapporte son coût (en tenant compte des coûts personnalisés)
+help-set-cost = Fixe (ou rétablit) le coût d'un arc d'une instance, p. ex. pour modéliser une route fermée, sans la régénérer
+help-forbid = Interdit (ou autorise à nouveau) un arc d'une instance, p. ex. pour modéliser un sens unique ou une règle métier
+help-require = Impose (ou n'impose plus) un arc dans tout tour d'une instance, p. ex. pour reproduire un plan partiellement fixé
+help-split-tour = Découpe un tour trop long pour un seul service en tournées journalières qui partent du dépôt et y reviennent, en visitant les destinations dans l'ordre du tour original
+help-links = Exporte un tour sous la forme d'une liste de liens de navigation, découpés pour respecter le nombre maximal d'étapes d'un lien
+help-compare-distribution = Compare les statistiques spatiales d'une instance à celles d'un jeu d'arrêts réels de référence, et rapporte leur divergence
+help-fit = Estime les paramètres du générateur à partir d'un jeu d'arrêts réels, et écrit la configuration produisant des instances statistiquement similaires
+help-anonymize = Déplace aléatoirement les destinations d'une instance dans un rayon et recalcule la matrice des coûts, afin de produire une instance similaire sans les adresses exactes des clients
+help-selftest = Résout les instances embarquées et vérifie que le solveur prouve leurs valeurs optimales connues (et que ses tours sont valides)
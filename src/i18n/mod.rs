@@ -0,0 +1,159 @@
+//! This module implements the localization of the texts meant for humans (the
+//! human readable results of the commands and the labels of the html maps). The
+//! translations are fluent resources which are embedded in the binary. They
+//! also cover the help of the command line, as far as its global options and
+//! the descriptions of the commands are concerned (the help of the options of
+//! each command is only available in english).
+
+use std::fmt::Display;
+
+use clap::{Command, ValueEnum};
+use fluent::{FluentArgs, FluentBundle, FluentResource, FluentValue};
+use serde_json::{Map, Value};
+use unic_langid::LanguageIdentifier;
+
+/// The languages in which the texts can be shown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Lang {
+    #[default]
+    En,
+    Fr,
+    Nl,
+}
+
+impl Lang {
+    /// Returns the (ISO 639-1) code of this language
+    pub fn code(&self) -> &'static str {
+        match self {
+            Lang::En => "en",
+            Lang::Fr => "fr",
+            Lang::Nl => "nl",
+        }
+    }
+
+    /// Returns the language requested by the `--lang` option among the given
+    /// command line arguments. This is needed to localize the help, which is
+    /// printed while the arguments are being parsed.
+    pub fn requested(args: &[String]) -> Self {
+        args.iter().enumerate()
+            .find_map(|(k, arg)| match arg.strip_prefix("--lang") {
+                Some("") => args.get(k + 1).map(String::as_str),
+                Some(value) => value.strip_prefix('='),
+                None => None,
+            })
+            .and_then(|code| Self::from_str(code, true).ok())
+            .unwrap_or_default()
+    }
+
+    /// Returns the fluent resource of this language
+    fn resource(&self) -> &'static str {
+        match self {
+            Lang::En => include_str!("en.ftl"),
+            Lang::Fr => include_str!("fr.ftl"),
+            Lang::Nl => include_str!("nl.ftl"),
+        }
+    }
+}
+
+/// The translated messages of one language
+pub struct Messages {
+    lang: Lang,
+    bundle: FluentBundle<FluentResource>,
+}
+
+impl std::fmt::Debug for Messages {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Messages").finish_non_exhaustive()
+    }
+}
+
+impl Default for Messages {
+    fn default() -> Self {
+        Self::new(Lang::default())
+    }
+}
+
+impl Messages {
+    /// Loads the messages of the given language
+    pub fn new(lang: Lang) -> Self {
+        let id = lang.code();
+        let langid: LanguageIdentifier = id.parse().unwrap();
+        let resource = FluentResource::try_new(lang.resource().to_string())
+            .unwrap_or_else(|_| panic!("the {id} translations are malformed"));
+        let mut bundle = FluentBundle::new(vec![langid]);
+        // the texts end up in terminals and html pages, where the isolation marks are noise
+        bundle.set_use_isolating(false);
+        bundle.add_resource(resource).unwrap();
+        Self { lang, bundle }
+    }
+
+    /// Returns the language of these messages
+    pub fn lang(&self) -> Lang {
+        self.lang
+    }
+
+    /// Returns the message having the given id
+    pub fn text(&self, id: &str) -> String {
+        self.format(id, &[])
+    }
+
+    /// Returns the word meaning `true` or `false`
+    pub fn boolean(&self, value: bool) -> String {
+        self.text(if value { "yes" } else { "no" })
+    }
+
+    /// Returns the label of a result, that is the message having the given id
+    /// without its value
+    pub fn label(&self, id: &str) -> String {
+        self.format(id, &[("value", &"")]).trim().to_string()
+    }
+
+    /// Returns the message having the given id, where the given arguments have
+    /// been substituted (they are formatted just like `format!` would do)
+    pub fn format(&self, id: &str, args: &[(&str, &dyn Display)]) -> String {
+        let message = self.bundle.get_message(id)
+            .and_then(|m| m.value())
+            .unwrap_or_else(|| panic!("there is no message {id}"));
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args.iter() {
+            fluent_args.set(*name, FluentValue::from(value.to_string()));
+        }
+        let mut errors = vec![];
+        self.bundle.format_pattern(message, Some(&fluent_args), &mut errors).into_owned()
+    }
+
+    /// Returns the given command where the descriptions of the program, of its
+    /// global options and of its subcommands are translated (the texts which
+    /// have no translation are left as they are)
+    pub fn localize(&self, mut command: Command) -> Command {
+        if let Some(about) = self.optional("help-about") {
+            command = command.about(about);
+        }
+        for arg in ["json", "quiet", "porcelain", "lang"] {
+            if let Some(help) = self.optional(&format!("help-{arg}")) {
+                command = command.mut_arg(arg, |a| a.help(help));
+            }
+        }
+        let names = command.get_subcommands().map(|c| c.get_name().to_string()).collect::<Vec<_>>();
+        for name in names {
+            if let Some(about) = self.optional(&format!("help-{name}")) {
+                command = command.mut_subcommand(name, |c| c.about(about));
+            }
+        }
+        command
+    }
+
+    /// Returns the message having the given id, if any
+    fn optional(&self, id: &str) -> Option<String> {
+        self.bundle.has_message(id).then(|| self.text(id))
+    }
+
+    /// Returns an object mapping each of the given ids onto its message (this is
+    /// meant to pass the labels of a page to a template)
+    pub fn labels(&self, ids: &[&str]) -> Value {
+        let labels = ids.iter()
+            .map(|id| (id.replace('-', "_"), Value::String(self.text(id))))
+            .collect::<Map<_, _>>();
+        Value::Object(labels)
+    }
+}
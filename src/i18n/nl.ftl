@@ -0,0 +1,86 @@
+## results of the commands
+is-exact = exact { $value }
+best-value = beste waarde { $value }
+valid = geldig { $value }
+value = waarde { $value }
+violation = overtreding: { $value }
+working-hours = werkuren { $value }
+fits-shift = past in de dienst { $value }
+nb-days = { $value } dagen
+day-summary = dag { $day }: { $stops } ({ $hours } u)
+split-needed = onderbreking nodig vóór bestemming { $destination } (positie { $position })
+overlong-leg = het traject { $from } -> { $to } (positie { $position }) past in geen enkele dienst
+selftest-case-ok = { $name } ok
+selftest-case-failed = { $name } MISLUKT
+selftest-passed = zelftest geslaagd
+selftest-failed = zelftest mislukt
+batch-outputs = { $generated } instanties gegenereerd, { $skipped } al aanwezig
+snapping = verplaatsing naar de weg min { $min } m gemiddeld { $mean } m max { $max } m, { $resampled } punten opnieuw getrokken
+completed = { $value } niet-routeerbare paren aangevuld via andere bestemmingen
+metricized = { $value } kosten verlaagd om de driehoeksongelijkheid te respecteren
+cost-value = kosten { $value }
+co2-value = CO₂ { $value } kg
+yes = ja
+no = nee
+wall-time = verstreken tijd { $value } s
+cpu-time = cpu-tijd { $value } s
+peak-rss = piekgeheugen { $value } kB
+explored-nodes = verkende knopen { $value }
+restricted-dds = beperkte diagrammen { $value }
+relaxed-dds = gerelaxeerde diagrammen { $value }
+phase = fase { $phase } breedte { $width } beste waarde { $value } exact { $exact } verstreken tijd { $time } s verkende knopen { $explored }
+none = geen
+solution = oplossing: { $value }
+lower-bound = ondergrens { $value }
+gap = kloof { $value }%
+initial-value = beginwaarde { $value }
+overrides = aangepaste kosten { $value }
+required = opgelegde verbindingen { $value }
+forbidden = verboden verbindingen { $value }
+fit-summary = { $stops } stops, { $clusters } clusters, standaardafwijking { $sigma } graden, { $outliers } uitschieters
+compare-instance = instantie
+compare-reference = referentie
+compare-stops = stops
+compare-mean-nearest = gem. dichtstbijzijnde (m)
+compare-mean-pairwise = gem. onderlinge afstand (m)
+compare-largest-cluster = grootste cluster
+divergence = divergentie: dichtstbijzijnde buren KS { $nearest }, onderlinge afstanden JS { $pairwise }, clustergroottes TV { $clusters }
+
+## labels of the maps
+distance-unit = km
+duration = { $hours } uur { $minutes } minuten { $seconds } seconden
+cost = kosten
+co2 = kg CO₂
+day = dag
+destination = bestemming
+elevation = hoogte
+legend = legende
+status-unchanged = ongewijzigd
+status-moved = verplaatst
+status-added = toegevoegd
+status-removed = verwijderd
+stop = stop
+map-description = Kaart van de bestemmingen. Druk op n of p om naar de volgende of vorige stop te gaan.
+profile-description = Hoogteprofiel langs de route
+
+## help of the command line
+help-about = Genereert TSP-instanties waarvan de bestemmingen in clusters gegroepeerd zijn
+help-json = Toont de resultaten van het commando als één json-document
+help-quiet = Toont niets (behalve fouten)
+help-porcelain = Toont de resultaten als stabiele, door tabs gescheiden `sleutel waarde`-regels
+help-lang = De taal van de teksten voor mensen (resultaten, kaarten en hulp)
+help-generate = Genereert realistische TSP-instanties waarvan de te bezoeken steden in clusters gegroepeerd zijn
+help-visualize = Genereert een html-bestand om een instantie en een eventuele oplossing te visualiseren
+help-solve = Lost een instantie op met branch-and-bound over beslissingsdiagrammen
+help-bound = Berekent een gecertificeerde ondergrens voor de lengte van de optimale tour en geeft die samen met een heuristische tour, zodat de optimaliteitskloof van die tour gekend is zonder de instantie exact op te lossen
+help-polish = Verbetert een bestaande tour (bv. van een ander hulpmiddel) met een in de tijd begrensde lokale zoektocht, en schrijft de verbeterde tour weg
+help-check = Controleert dat een oplossing elke verplichte bestemming van een instantie precies één keer bezoekt (en de optionele hoogstens één keer) en geeft haar kosten (rekening houdend met de aangepaste kosten)
+help-set-cost = Stelt de kosten van één verbinding van een instantie in (of herstelt ze), bv. om een afgesloten weg te modelleren, zonder ze opnieuw te genereren
+help-forbid = Verbiedt (of staat opnieuw toe) één verbinding van een instantie, bv. om eenrichtingsverkeer of een bedrijfsregel te modelleren
+help-require = Legt één verbinding op in elke tour van een instantie (of niet langer), bv. om een gedeeltelijk vastgelegde planning na te bootsen
+help-split-tour = Verdeelt een tour die te lang is voor één dienst in dagtours die bij het depot beginnen en eindigen, in de volgorde van de oorspronkelijke tour
+help-links = Exporteert een tour als een lijst navigatielinks, opgesplitst om het maximale aantal tussenstops van een link te respecteren
+help-compare-distribution = Vergelijkt de ruimtelijke statistieken van een instantie met die van een referentieset van echte stops, en geeft aan hoe sterk ze verschillen
+help-fit = Schat de parameters van de generator op basis van een set echte stops, en schrijft de configuratie die statistisch gelijkaardige instanties oplevert
+help-anonymize = Verschuift de bestemmingen van een instantie willekeurig binnen een straal en herberekent de kostenmatrix, om een gelijkaardige instantie zonder de exacte klantadressen te bekomen
+help-selftest = Lost de meegeleverde instanties op en controleert dat de solver hun gekende optimale waarden bewijst (en dat zijn tours geldig zijn)
@@ -2,20 +2,222 @@
 //! a set of locations along with a metric "distance" (time or distance) to reach
 //! each location from each other.
 
+use std::collections::BTreeMap;
+
+use clap::ValueEnum;
 use osrm_client::{Location, GeoJsonGeometry, GeoJsonPoint};
 use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::{error::ParseError, postprocess::Symmetrize};
 
 /// A TSP instance that knows the gps coordinates of the destinations that must
 /// be visited along with the distances to travel from one city to the other.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Instance {
     /// The gps coordinates of the places that must be visited.
     pub destinations: Vec<Location>,
     /// The distance (in metres) between all pairs of destinations
     pub distances: Vec<Vec<f32>>,
+    /// The travel cost matrices of the other routing profiles (e.g. bike), by name.
+    /// The distances are those of the first profile listed in the metadata.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Vec<Vec<f32>>>,
+    /// The index of the cluster (centroid) each destination was sampled around.
+    /// This is empty for instances which have been generated without clusters.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clusters: Vec<usize>,
+    /// Information about the way this instance has been generated
+    #[serde(default)]
+    pub metadata: Metadata,
+    /// Custom travel costs which replace those of the distance matrix for some
+    /// edges (e.g. to model a closed road or a penalized connection)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overrides: Vec<CostOverride>,
+    /// The (from, to) edges along which it is forbidden to travel
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forbidden: Vec<(usize, usize)>,
+    /// The (from, to) edges which must be part of any tour (fixed legs)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<(usize, usize)>,
+    /// Whether each destination is an optional waypoint which a tour may skip.
+    /// This is empty when all destinations are mandatory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub optional: Vec<bool>,
+    /// The kind of tour which is sought for this instance
+    #[serde(default, skip_serializing_if = "Variant::is_tsp")]
+    pub variant: Variant,
+}
+
+/// The problems that can be posed on a set of (clustered) destinations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Variant {
+    /// Each destination must be visited exactly once
+    #[default]
+    Tsp,
+    /// Generalized TSP: exactly one destination of each cluster must be visited.
+    /// The cluster of the depot is visited by the depot itself.
+    Gtsp,
+}
+
+impl Variant {
+    /// Returns true iff this is the plain TSP variant
+    pub fn is_tsp(&self) -> bool {
+        *self == Variant::Tsp
+    }
+}
+
+/// A custom travel cost for the edge going from one destination to another
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CostOverride {
+    pub from: usize,
+    pub to: usize,
+    pub cost: f32,
+}
+
+/// Information about the way an instance has been generated
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Metadata {
+    /// The (routable) centroids around which the destinations were sampled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub centroids: Vec<Location>,
+    /// Whether the travel costs are durations (in seconds) rather than distances
+    /// (in metres)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub durations: bool,
+    /// The rule used to make the distance matrix symmetric (if any)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symmetrized: Option<Symmetrize>,
+    /// The number of costs of the distance matrix which have been lowered to
+    /// enforce the triangle inequality (if that has been done)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metricized: Option<usize>,
+    /// The (from, to) pairs that OSRM could not route, and whose cost is that of
+    /// the shortest path through other destinations
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub completed: Vec<(usize, usize)>,
+    /// The distance (in metres) by which each destination was moved when it was
+    /// snapped to the nearest routable point (empty if they were not snapped)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub snap_distances: Vec<f32>,
+    /// Summary of the displacements caused by the snapping (if it happened)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapping: Option<SnapStats>,
+    /// The routing profiles whose travel costs are stored in the instance, the
+    /// first of which gives the distances (empty if only the default one was used)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<String>,
+    /// The postprocessing of the matrices of the other routing profiles, by name
+    /// (the fields above describe that of the distances)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub postprocessing: BTreeMap<String, Postprocessing>,
+}
+
+/// How the travel cost matrix of a routing profile was postprocessed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Postprocessing {
+    /// The number of costs lowered to enforce the triangle inequality (if that
+    /// has been done)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metricized: Option<usize>,
+    /// The (from, to) pairs that OSRM could not route
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub completed: Vec<(usize, usize)>,
+}
+
+/// How far the locations were moved when they were snapped to the nearest routable
+/// point, which tells how faithful the instance is to the intended distribution
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SnapStats {
+    /// The smallest displacement (in metres)
+    pub min: f32,
+    /// The mean displacement (in metres)
+    pub mean: f32,
+    /// The largest displacement (in metres)
+    pub max: f32,
+    /// The number of locations which were sampled anew because they lay too far
+    /// from any road
+    pub resampled: usize,
+}
+
+impl SnapStats {
+    /// Summarizes the given displacements, or returns None if there are none
+    pub fn new(distances: &[f32], resampled: usize) -> Option<Self> {
+        if distances.is_empty() {
+            return None;
+        }
+        Some(SnapStats {
+            min: distances.iter().copied().fold(f32::INFINITY, f32::min),
+            mean: distances.iter().sum::<f32>() / distances.len() as f32,
+            max: distances.iter().copied().fold(0.0, f32::max),
+            resampled,
+        })
+    }
 }
 
 impl Instance {
+    /// Parses an instance from its json text. The instance may either be stored as
+    /// is, or be the `instance` field of a json document (such as the one printed by
+    /// `generate --json`). The instance is checked for consistency.
+    pub fn from_json(text: &str) -> Result<Self, ParseError> {
+        let mut doc: Value = serde_json::from_str(text)?;
+        if let Some(inner) = doc.get_mut("instance").filter(|i| i.is_object()) {
+            doc = inner.take();
+        }
+        let instance: Instance = serde_json::from_value(doc)?;
+        instance.validate()?;
+        Ok(instance)
+    }
+
+    /// Checks that this instance is consistent: it has a square distance matrix
+    /// without negative (or NaN) costs, and all its annotations refer to existing
+    /// destinations.
+    pub fn validate(&self) -> Result<(), ParseError> {
+        let n = self.len();
+        let invalid = |message: String| Err(ParseError::Invalid(message));
+        let exists = |x: usize| x < n;
+        let valid_cost = |c: f32| !c.is_nan() && c >= 0.0;
+
+        if n == 0 {
+            return invalid("there is no destination".to_string());
+        }
+        if self.distances.len() != n || self.distances.iter().any(|row| row.len() != n) {
+            return invalid(format!("the distance matrix is not {n} x {n}"));
+        }
+        if !self.distances.iter().flatten().all(|c| valid_cost(*c)) {
+            return invalid("the distance matrix has negative or undefined costs".to_string());
+        }
+        for (name, matrix) in self.profiles.iter() {
+            if matrix.len() != n || matrix.iter().any(|row| row.len() != n) {
+                return invalid(format!("the matrix of the profile {name} is not {n} x {n}"));
+            }
+            if !matrix.iter().flatten().all(|c| valid_cost(*c)) {
+                return invalid(format!("the matrix of the profile {name} has negative or undefined costs"));
+            }
+        }
+        if !self.clusters.is_empty() && self.clusters.len() != n {
+            return invalid(format!("there are {} cluster labels for {n} destinations", self.clusters.len()));
+        }
+        if let Some(c) = self.clusters.iter().find(|c| !exists(**c)) {
+            return invalid(format!("the cluster label {c} exceeds the number of destinations"));
+        }
+        if !self.optional.is_empty() && self.optional.len() != n {
+            return invalid(format!("there are {} optional flags for {n} destinations", self.optional.len()));
+        }
+        if self.variant == Variant::Gtsp && self.clusters.is_empty() {
+            return invalid("a gtsp instance needs cluster labels".to_string());
+        }
+        if let Some(o) = self.overrides.iter().find(|o| !exists(o.from) || !exists(o.to) || !valid_cost(o.cost)) {
+            return invalid(format!("the cost override {} -> {} ({}) is invalid", o.from, o.to, o.cost));
+        }
+        let edges = self.forbidden.iter().chain(self.required.iter());
+        if let Some((a, b)) = edges.copied().find(|(a, b)| !exists(*a) || !exists(*b)) {
+            return invalid(format!("the edge {a} -> {b} leads to a nonexistent destination"));
+        }
+        Ok(())
+    }
+
     /// Generates a string corresponding a description of the instance in the form 
     /// which is usually used to encode TSP instances
     #[allow(dead_code)]
@@ -37,6 +239,218 @@ impl Instance {
         result
     }
 
+    /// Returns the number of destinations in this instance
+    pub fn len(&self) -> usize {
+        self.destinations.len()
+    }
+
+    /// Returns a fingerprint of the content of this instance (the 64 bits FNV-1a hash
+    /// of its compact json text, in hexadecimal). Unlike the hashers of the standard
+    /// library, it is stable across platforms and versions.
+    pub fn fingerprint(&self) -> String {
+        let text = serde_json::to_string(self).unwrap();
+        let hash = text.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |h, b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3));
+        format!("{hash:016x}")
+    }
+
+    /// Returns true iff there is no destination at all in this instance
+    pub fn is_empty(&self) -> bool {
+        self.destinations.is_empty()
+    }
+
+    /// Returns the sub instance which only comprises the given destinations (in
+    /// that order). The first of these becomes the depot of the sub instance. The
+    /// postprocessing metadata is renumbered accordingly, but for the numbers of
+    /// lowered costs which only hold for the whole matrices: they are cleared.
+    pub fn subset(&self, ids: &[usize]) -> Instance {
+        let index = |x: usize| ids.iter().position(|i| *i == x);
+        let edges = |edges: &[(usize, usize)]| edges.iter()
+            .filter_map(|(a, b)| Some((index(*a)?, index(*b)?)))
+            .collect::<Vec<_>>();
+        let snap_distances = if self.metadata.snap_distances.is_empty() {
+            vec![]
+        } else {
+            ids.iter().map(|i| self.metadata.snap_distances[*i]).collect()
+        };
+        Instance {
+            destinations: ids.iter().map(|i| self.destinations[*i]).collect(),
+            distances: ids.iter()
+                .map(|i| ids.iter().map(|j| self.distances[*i][*j]).collect())
+                .collect(),
+            profiles: self.profiles.iter()
+                .map(|(name, matrix)| (name.clone(), ids.iter()
+                    .map(|i| ids.iter().map(|j| matrix[*i][*j]).collect())
+                    .collect()))
+                .collect(),
+            clusters: if self.clusters.is_empty() {
+                vec![]
+            } else {
+                ids.iter().map(|i| self.clusters[*i]).collect()
+            },
+            metadata: Metadata {
+                metricized: None,
+                completed: edges(&self.metadata.completed),
+                snapping: self.metadata.snapping.and_then(|s| SnapStats::new(&snap_distances, s.resampled)),
+                snap_distances,
+                postprocessing: self.metadata.postprocessing.iter()
+                    .map(|(name, p)| (name.clone(), Postprocessing { metricized: None, completed: edges(&p.completed) }))
+                    .collect(),
+                ..self.metadata.clone()
+            },
+            overrides: self.overrides.iter()
+                .filter_map(|o| Some(CostOverride { from: index(o.from)?, to: index(o.to)?, cost: o.cost }))
+                .collect(),
+            forbidden: edges(&self.forbidden),
+            required: edges(&self.required),
+            optional: if self.optional.is_empty() {
+                vec![]
+            } else {
+                ids.iter().map(|i| self.optional[*i]).collect()
+            },
+            variant: self.variant,
+        }
+    }
+
+    /// Returns the actual travel costs between all pairs of destinations, that is
+    /// the distance matrix where the cost overrides have been applied. The edges
+    /// which may not be used have an infinite cost.
+    pub fn costs(&self) -> Vec<Vec<f32>> {
+        let mut costs = self.distances.clone();
+        for o in self.overrides.iter() {
+            costs[o.from][o.to] = o.cost;
+        }
+        for (from, row) in costs.iter_mut().enumerate() {
+            for (to, cost) in row.iter_mut().enumerate() {
+                if from != to && !self.is_allowed(from, to) {
+                    *cost = f32::INFINITY;
+                }
+            }
+        }
+        costs
+    }
+
+    /// Returns the destinations whose visit is fulfilled by a visit to the given one.
+    /// This is the destination itself for a TSP, and its whole cluster for a GTSP.
+    pub fn covered_by(&self, i: usize) -> Vec<usize> {
+        match self.variant {
+            Variant::Tsp => vec![i],
+            Variant::Gtsp => {
+                assert_eq!(self.clusters.len(), self.len(), "a gtsp instance needs cluster labels");
+                (0..self.len()).filter(|j| self.clusters[*j] == self.clusters[i]).collect()
+            },
+        }
+    }
+
+    /// Generates the description of this (generalized) instance in the GTSPLIB
+    /// format. The travel costs are rounded to the nearest integer and the nodes
+    /// are numbered from 1 on.
+    pub fn gtsplib_text(&self, name: &str) -> String {
+        let members = self.cluster_members().expect("a gtsp instance needs cluster labels");
+        let costs = self.costs();
+        let symmetric = (0..self.len()).all(|i| (0..i).all(|j| costs[i][j] == costs[j][i]));
+
+        let mut result = String::new();
+        result.push_str(&format!("NAME: {name}\n"));
+        result.push_str(&format!("TYPE: {}\n", if symmetric { "GTSP" } else { "AGTSP" }));
+        result.push_str("COMMENT: generated with tspgen (https://github.com/xgillard/tspgen)\n");
+        result.push_str(&format!("DIMENSION: {}\n", self.len()));
+        result.push_str(&format!("GTSP_SETS: {}\n", members.len()));
+        result.push_str("EDGE_WEIGHT_TYPE: EXPLICIT\n");
+        result.push_str("EDGE_WEIGHT_FORMAT: FULL_MATRIX\n");
+        result.push_str("EDGE_WEIGHT_SECTION\n");
+        for row in costs.iter() {
+            // the edges that cannot be used get a prohibitive cost
+            let row = row.iter()
+                .map(|c| if c.is_finite() { format!("{:.0}", c) } else { i32::MAX.to_string() })
+                .collect::<Vec<_>>();
+            result.push_str(&row.join(" "));
+            result.push('\n');
+        }
+        result.push_str("GTSP_SET_SECTION\n");
+        for (k, ids) in members.iter().enumerate() {
+            result.push_str(&format!("{}", k + 1));
+            ids.iter().for_each(|i| result.push_str(&format!(" {}", i + 1)));
+            result.push_str(" -1\n");
+        }
+        result.push_str("EOF\n");
+        result
+    }
+
+    /// Returns true iff the given destination may be skipped by a tour (the depot
+    /// never is optional)
+    pub fn is_optional(&self, i: usize) -> bool {
+        i != 0 && self.optional.get(i).copied().unwrap_or(false)
+    }
+
+    /// Returns true iff it is forbidden to travel from -> to
+    pub fn is_forbidden(&self, from: usize, to: usize) -> bool {
+        self.forbidden.contains(&(from, to))
+    }
+
+    /// Returns true iff a tour may travel from -> to. This is the case unless the
+    /// edge is forbidden, or it conflicts with a required edge (leaving `from`
+    /// towards another destination, or reaching `to` from another destination).
+    pub fn is_allowed(&self, from: usize, to: usize) -> bool {
+        !self.is_forbidden(from, to) && self.required.iter()
+            .all(|(a, b)| (*a == from) == (*b == to))
+    }
+
+    /// Makes the matrix of the given routing profile the distances of this instance
+    /// (the former distances are kept aside under the name of their own profile,
+    /// and so is the description of their postprocessing). The error tells which
+    /// profiles are available when the instance has no matrix for the given one.
+    pub fn use_profile(&mut self, name: &str) -> Result<(), String> {
+        if self.metadata.profiles.first().is_some_and(|p| p == name) {
+            return Ok(());
+        }
+        let Some(matrix) = self.profiles.remove(name) else {
+            let known = if self.metadata.profiles.is_empty() { "none".to_string() } else { self.metadata.profiles.join(", ") };
+            return Err(format!("the instance has no matrix for the profile {name} (available profiles: {known})"));
+        };
+        let current = self.metadata.profiles.first().cloned().unwrap_or_else(|| "default".to_string());
+        self.profiles.insert(current.clone(), std::mem::replace(&mut self.distances, matrix));
+
+        let metadata = &mut self.metadata;
+        let postprocessing = metadata.postprocessing.remove(name).unwrap_or_default();
+        let former = Postprocessing {
+            metricized: std::mem::replace(&mut metadata.metricized, postprocessing.metricized),
+            completed: std::mem::replace(&mut metadata.completed, postprocessing.completed),
+        };
+        metadata.postprocessing.insert(current, former);
+        if let Some(k) = metadata.profiles.iter().position(|p| p == name) {
+            metadata.profiles.swap(0, k);
+        }
+        Ok(())
+    }
+
+    /// Sets a custom travel cost for the edge from -> to (replacing any previous one)
+    pub fn set_cost(&mut self, from: usize, to: usize, cost: f32) {
+        self.overrides.retain(|o| o.from != from || o.to != to);
+        self.overrides.push(CostOverride { from, to, cost });
+    }
+
+    /// Removes the custom travel cost of the edge from -> to (if any)
+    pub fn reset_cost(&mut self, from: usize, to: usize) {
+        self.overrides.retain(|o| o.from != from || o.to != to);
+    }
+
+    /// Returns the destinations grouped by cluster (the cluster of the depot coming
+    /// first), or None when the instance carries no cluster labels
+    pub fn cluster_members(&self) -> Option<Vec<Vec<usize>>> {
+        if self.clusters.len() != self.len() || self.is_empty() {
+            return None;
+        }
+        let nb_clusters = self.clusters.iter().copied().max().unwrap_or(0) + 1;
+        let mut members = vec![vec![]; nb_clusters];
+        for (i, c) in self.clusters.iter().copied().enumerate() {
+            members[c].push(i);
+        }
+        members.retain(|m| !m.is_empty());
+        let depot = members.iter().position(|m| m.contains(&0)).unwrap();
+        members.swap(0, depot);
+        Some(members)
+    }
+
     /// Returns a geojson multipoint geometry where each point is one of the destinations
     /// to be visited
     pub fn geojson(&self) -> GeoJsonGeometry {
@@ -45,3 +459,56 @@ impl Instance {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cluster_labels_are_bounded() {
+        let instance = |clusters: &str| format!(r#"{{
+            "destinations": [{{"longitude": 4.35, "latitude": 50.85}}, {{"longitude": 4.40, "latitude": 50.90}}],
+            "distances": [[0, 1], [1, 0]],
+            "clusters": {clusters}
+        }}"#);
+        assert!(Instance::from_json(&instance("[0, 1]")).is_ok());
+        assert!(Instance::from_json(&instance("[0, 1000000000000000000]")).is_err());
+    }
+
+    #[test]
+    fn profiles_are_swapped_along_with_their_postprocessing() {
+        let mut instance = Instance::from_json(r#"{
+            "destinations": [{"longitude": 4.35, "latitude": 50.85}, {"longitude": 4.40, "latitude": 50.90}],
+            "distances": [[0, 1], [1, 0]],
+            "profiles": {"bike": [[0, 2], [3, 0]]},
+            "metadata": {"profiles": ["car", "bike"], "metricized": 4, "postprocessing": {"bike": {"completed": [[0, 1]]}}}
+        }"#).unwrap();
+        assert_eq!(instance.use_profile("foot"), Err("the instance has no matrix for the profile foot (available profiles: car, bike)".to_string()));
+        instance.use_profile("bike").unwrap();
+        assert_eq!(instance.distances, [[0.0, 2.0], [3.0, 0.0]]);
+        assert_eq!(instance.metadata.completed, [(0, 1)]);
+        assert_eq!(instance.metadata.metricized, None);
+        assert_eq!(instance.metadata.postprocessing["car"], Postprocessing { metricized: Some(4), completed: vec![] });
+        assert_eq!(instance.profiles["car"], [[0.0, 1.0], [1.0, 0.0]]);
+    }
+
+    #[test]
+    fn subsets_renumber_their_postprocessing() {
+        let instance = Instance::from_json(r#"{
+            "destinations": [{"longitude": 4.35, "latitude": 50.85}, {"longitude": 4.40, "latitude": 50.90}, {"longitude": 4.45, "latitude": 50.95}],
+            "distances": [[0, 1, 2], [1, 0, 1], [2, 1, 0]],
+            "profiles": {"bike": [[0, 2, 4], [2, 0, 2], [4, 2, 0]]},
+            "metadata": {
+                "profiles": ["car", "bike"], "metricized": 4, "completed": [[0, 1], [2, 1]],
+                "snap_distances": [10, 20, 30], "snapping": {"min": 10, "mean": 20, "max": 30, "resampled": 1},
+                "postprocessing": {"bike": {"metricized": 2, "completed": [[1, 0], [2, 0]]}}
+            }
+        }"#).unwrap();
+        let subset = instance.subset(&[2, 1]);
+        assert_eq!(subset.metadata.completed, [(0, 1)]);
+        assert_eq!(subset.metadata.metricized, None);
+        assert_eq!(subset.metadata.snap_distances, [30.0, 20.0]);
+        assert_eq!(subset.metadata.snapping, Some(SnapStats { min: 20.0, mean: 25.0, max: 30.0, resampled: 1 }));
+        assert_eq!(subset.metadata.postprocessing["bike"], Postprocessing { metricized: None, completed: vec![] });
+    }
+}
@@ -0,0 +1,104 @@
+//! This module provides the command which exports a tour as navigation links, so
+//! that small tours can be driven directly from a phone.
+
+use clap::{Args, ValueEnum};
+use osrm_client::Location;
+
+use crate::{report::Report, files, check};
+
+/// The kinds of navigation links
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LinkKind {
+    /// Google Maps directions URLs going through the destinations as waypoints
+    Google,
+    /// One `geo:` URI per destination, in the order of the tour
+    Geo,
+}
+
+/// This command exports a tour as a list of navigation deep links. Long tours are
+/// chunked in several links (each one starting where the previous one stopped) so
+/// as to respect the maximum number of waypoints of a link.
+#[derive(Debug, Args)]
+pub struct Links {
+    /// The path to the instance file (`-` for the standard input)
+    #[clap(short, long)]
+    pub instance: String,
+    /// The tour to export (sequence of destination identifiers 0..n), or the path
+    /// of a file comprising it (`-` for the standard input)
+    #[clap(short, long)]
+    pub solution: String,
+    /// The kind of links to produce
+    #[clap(short, long, value_enum, default_value_t=LinkKind::Google)]
+    pub kind: LinkKind,
+    /// The maximum number of intermediate waypoints of one link (google links only)
+    #[clap(short='w', long, default_value="9")]
+    pub max_waypoints: usize,
+    /// If present, the path where to write the links, one per line (`-` for the
+    /// standard output)
+    #[clap(short, long)]
+    pub output: Option<String>,
+    /// Overwrite the output file if it already exists
+    #[clap(long)]
+    pub force: bool,
+}
+
+impl Links {
+    /// Executes this command
+    pub async fn execute(&self, report: &mut Report) {
+        if let Some(output) = self.output.as_ref() {
+            files::check_output(output, self.force);
+        }
+        let instance = files::read_instance(&self.instance);
+        let tour = files::read_solution(&self.solution).tour;
+        check::ensure_valid(&instance, &tour, &self.solution);
+        let stops = tour.iter().chain(tour.first())
+            .map(|i| instance.destinations[*i])
+            .collect::<Vec<_>>();
+
+        let links = match self.kind {
+            LinkKind::Google => google_links(&stops, self.max_waypoints),
+            LinkKind::Geo => stops[..stops.len().saturating_sub(1)].iter().map(|l| geo_uri(*l)).collect(),
+        };
+
+        if let Some(output) = self.output.as_ref() {
+            files::write_text(output, &(links.join("\n") + "\n"), self.force);
+            report.json("output", output);
+        } else {
+            links.iter().for_each(|l| report.line(l));
+        }
+        report.json("links", links);
+    }
+}
+
+/// Returns the Google Maps directions URLs driving along the given stops. Each
+/// link has at most `max_waypoints` waypoints between its origin and destination.
+pub fn google_links(stops: &[Location], max_waypoints: usize) -> Vec<String> {
+    let per_link = max_waypoints + 1;
+    let mut links = vec![];
+    let mut start = 0;
+    while start + 1 < stops.len() {
+        let end = (start + per_link).min(stops.len() - 1);
+        let waypoints = stops[start + 1..end].iter()
+            .map(|l| coordinates(*l))
+            .collect::<Vec<_>>()
+            .join("%7C");
+        let mut link = format!("https://www.google.com/maps/dir/?api=1&travelmode=driving&origin={}&destination={}",
+            coordinates(stops[start]), coordinates(stops[end]));
+        if !waypoints.is_empty() {
+            link.push_str(&format!("&waypoints={waypoints}"));
+        }
+        links.push(link);
+        start = end;
+    }
+    links
+}
+
+/// Returns the `geo:` URI of the given location
+pub fn geo_uri(location: Location) -> String {
+    format!("geo:{}", coordinates(location))
+}
+
+/// Returns the `latitude,longitude` text of the given location
+fn coordinates(Location { longitude, latitude }: Location) -> String {
+    format!("{latitude:.6},{longitude:.6}")
+}
@@ -1,10 +1,42 @@
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use i18n::{Lang, Messages};
+use report::{Report, Format};
+use anonymize::Anonymize;
+use check::Check;
+use compare::CompareDistribution;
+use edit::{SetCost, Forbid, Require};
+use fit::Fit;
 use generation::GenerateInstance;
-use resolution::Solve;
+use links::Links;
+use selftest::SelfTest;
+use split::SplitTour;
+use resolution::{Solve, Bound, Polish};
 use visualisation::Visualize;
 
 
+mod anonymize;
+mod check;
+mod compare;
+mod edit;
+mod error;
+mod elevation;
+mod files;
+mod fit;
+mod i18n;
+mod footprint;
+mod geocoding;
 mod instance;
+mod links;
+mod manifest;
+mod postprocess;
+mod presets;
+mod project;
+mod sampling;
+mod selftest;
+mod report;
+mod solution;
+mod split;
+mod stops;
 mod generation;
 mod visualisation;
 mod resolution;
@@ -19,23 +51,78 @@ mod resolution;
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
 struct TspTools {
+    /// Print the results of the command as a single json document
+    #[clap(long, global=true, conflicts_with_all=["quiet", "porcelain"])]
+    json: bool,
+    /// Do not print anything (apart from errors)
+    #[clap(short, long, global=true, conflicts_with="porcelain")]
+    quiet: bool,
+    /// Print the results as stable tab separated `key value` lines
+    #[clap(long, global=true)]
+    porcelain: bool,
+    /// The language of the texts meant for humans (results, maps and help)
+    #[clap(long, global=true, value_enum, default_value_t=Lang::En)]
+    lang: Lang,
     #[command(subcommand)]
     command: Command,
 }
 
+impl TspTools {
+    /// Returns the format in which the results must be printed
+    fn format(&self) -> Format {
+        if self.json {
+            Format::Json
+        } else if self.porcelain {
+            Format::Porcelain
+        } else if self.quiet {
+            Format::Quiet
+        } else {
+            Format::Human
+        }
+    }
+}
+
 #[derive(Debug, Subcommand)]
 enum Command {
     Generate(GenerateInstance),
     Visualize(Visualize),
-    Solve(Solve)
+    Solve(Solve),
+    Bound(Bound),
+    Polish(Polish),
+    Check(Check),
+    SetCost(SetCost),
+    Forbid(Forbid),
+    Require(Require),
+    SplitTour(SplitTour),
+    Links(Links),
+    CompareDistribution(CompareDistribution),
+    Fit(Fit),
+    Anonymize(Anonymize),
+    Selftest(SelfTest),
 }
 
 #[tokio::main]
 async fn main() {
-    let cli = TspTools::parse();
+    let args = std::env::args().collect::<Vec<_>>();
+    let command = Messages::new(Lang::requested(&args)).localize(TspTools::command());
+    let cli = TspTools::from_arg_matches(&command.get_matches_from(args)).unwrap_or_else(|e| e.exit());
+    let mut report = Report::new(cli.format(), cli.lang);
     match cli.command {
-        Command::Generate(generate) => generate.execute().await,
-        Command::Visualize(visualize) => visualize.execute().await,
-        Command::Solve(solve) => solve.execute().await
+        Command::Generate(generate) => generate.execute(&mut report).await,
+        Command::Visualize(visualize) => visualize.execute(&mut report).await,
+        Command::Solve(solve) => solve.execute(&mut report).await,
+        Command::Bound(bound) => bound.execute(&mut report).await,
+        Command::Polish(polish) => polish.execute(&mut report).await,
+        Command::Check(check) => check.execute(&mut report).await,
+        Command::SetCost(set_cost) => set_cost.execute(&mut report).await,
+        Command::Forbid(forbid) => forbid.execute(&mut report).await,
+        Command::Require(require) => require.execute(&mut report).await,
+        Command::SplitTour(split) => split.execute(&mut report).await,
+        Command::Links(links) => links.execute(&mut report).await,
+        Command::CompareDistribution(compare) => compare.execute(&mut report).await,
+        Command::Fit(fit) => fit.execute(&mut report).await,
+        Command::Anonymize(anonymize) => anonymize.execute(&mut report).await,
+        Command::Selftest(selftest) => selftest.execute(&mut report).await,
     }
+    report.print();
 }
\ No newline at end of file
@@ -0,0 +1,92 @@
+//! This module describes the manifest of a suite of instances (a batch or a scale
+//! series). The manifest is written as `manifest.json` next to the instances, and
+//! it lists every instance with its seed, fingerprint and a few statistics, so that
+//! the suite can be consumed without globbing its directory. For now, its only
+//! reader is `generate` itself: a batch which resumes takes the description of the
+//! instances generated by the previous run from its manifest.
+
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::{instance::Instance, files};
+
+/// The name of the manifest file of a suite
+pub const MANIFEST: &str = "manifest.json";
+
+/// The description of a suite of instances
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The parameters of the generator which produced the suite
+    pub parameters: Value,
+    /// The instances of the suite
+    pub instances: Vec<Entry>,
+}
+
+/// The description of one instance of a suite
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// The path of the instance, relative to the manifest
+    pub path: String,
+    /// The seed which produced the instance (as text, since it is a 128 bits integer)
+    pub seed: String,
+    /// A fingerprint of the content of the instance
+    pub fingerprint: String,
+    /// The number of destinations
+    pub destinations: usize,
+    /// The number of clusters (0 without cluster labels)
+    pub clusters: usize,
+    /// The mean travel cost between two distinct destinations
+    pub mean_cost: f32,
+}
+
+impl Entry {
+    /// Describes the instance written at the given path
+    pub fn new(path: &str, seed: u128, instance: &Instance) -> Self {
+        let n = instance.len();
+        let total = instance.distances.iter().flatten().filter(|c| c.is_finite()).sum::<f32>();
+        Entry {
+            path: Path::new(path).file_name().map(|f| f.to_string_lossy().into_owned()).unwrap_or_default(),
+            seed: seed.to_string(),
+            fingerprint: instance.fingerprint(),
+            destinations: n,
+            clusters: instance.cluster_members().map_or(0, |m| m.len()),
+            mean_cost: if n > 1 { total / (n * (n - 1)) as f32 } else { 0.0 },
+        }
+    }
+}
+
+impl Manifest {
+    /// Returns the path of the manifest of the suite whose instances are written
+    /// at the given path
+    pub fn path(output: &str) -> String {
+        Path::new(output).with_file_name(MANIFEST).to_string_lossy().into_owned()
+    }
+
+    /// Writes this manifest in the directory of the given instance path, and
+    /// returns the path of the manifest. An existing manifest is replaced, since
+    /// it describes a previous run of the same suite.
+    pub fn write(&self, output: &str) -> String {
+        let path = Self::path(output);
+        files::write_text(&path, &serde_json::to_string_pretty(self).unwrap(), true);
+        path
+    }
+
+    /// Returns the entry describing the instance of the given seed written at the
+    /// given path, if this manifest lists it
+    pub fn entry(&self, path: &str, seed: u128) -> Option<Entry> {
+        let name = Path::new(path).file_name()?.to_string_lossy();
+        self.instances.iter()
+            .find(|e| e.path == name && e.seed == seed.to_string())
+            .cloned()
+    }
+
+    /// Returns the paths of the instances of this suite, given the path of the
+    /// manifest itself
+    pub fn instance_paths(&self, manifest: &str) -> Vec<String> {
+        self.instances.iter()
+            .map(|e| Path::new(manifest).with_file_name(&e.path).to_string_lossy().into_owned())
+            .collect()
+    }
+}
@@ -0,0 +1,98 @@
+//! This module gathers the post-processings which can be applied to the travel cost
+//! matrix returned by OSRM before it is stored in an instance.
+
+use clap::ValueEnum;
+use serde::{Serialize, Deserialize};
+
+/// The rules to make a matrix symmetric: the costs of both directions of an edge
+/// are replaced by their min, max or average
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Symmetrize {
+    Min,
+    Max,
+    Avg,
+}
+
+/// Makes the given matrix symmetric according to the given rule
+#[allow(clippy::needless_range_loop)]
+pub fn symmetrize(matrix: &mut [Vec<f32>], rule: Symmetrize) {
+    let n = matrix.len();
+    for i in 0..n {
+        for j in 0..i {
+            let (a, b) = (matrix[i][j], matrix[j][i]);
+            let cost = match rule {
+                Symmetrize::Min => a.min(b),
+                Symmetrize::Max => a.max(b),
+                Symmetrize::Avg => (a + b) / 2.0,
+            };
+            matrix[i][j] = cost;
+            matrix[j][i] = cost;
+        }
+    }
+}
+
+/// Returns the costs of the shortest paths between all pairs of destinations
+/// (Floyd-Warshall), the paths going through any intermediate destinations
+#[allow(clippy::needless_range_loop)]
+pub fn shortest_paths(matrix: &[Vec<f32>]) -> Vec<Vec<f32>> {
+    let n = matrix.len();
+    let mut dist = matrix.to_vec();
+    for k in 0..n {
+        for i in 0..n {
+            let via = dist[i][k];
+            if via.is_infinite() {
+                continue;
+            }
+            for j in 0..n {
+                let cost = via + dist[k][j];
+                if cost < dist[i][j] {
+                    dist[i][j] = cost;
+                }
+            }
+        }
+    }
+    dist
+}
+
+/// Enforces the triangle inequality on the given matrix by replacing each cost by
+/// that of the shortest path between the same destinations. Returns the number of
+/// cells which have changed.
+pub fn metricize(matrix: &mut [Vec<f32>]) -> usize {
+    let closure = shortest_paths(matrix);
+    let mut changed = 0;
+    for (row, closed) in matrix.iter_mut().zip(closure) {
+        for (cost, shortest) in row.iter_mut().zip(closed) {
+            if shortest < *cost {
+                *cost = shortest;
+                changed += 1;
+            }
+        }
+    }
+    changed
+}
+
+/// Completes the costs of the pairs of destinations that cannot be reached from one
+/// another (infinite cost) with the cost of the shortest path through reachable
+/// intermediate destinations. Returns the pairs which have been completed, or an
+/// error naming the destination which cannot be reached at all from another one.
+pub fn complete(matrix: &mut [Vec<f32>]) -> Result<Vec<(usize, usize)>, String> {
+    let unreachable = |m: &[Vec<f32>]| m.iter().enumerate()
+        .flat_map(|(i, row)| row.iter().enumerate()
+            .filter(|(_, c)| c.is_infinite())
+            .map(move |(j, _)| (i, j)))
+        .collect::<Vec<_>>();
+
+    let pairs = unreachable(matrix);
+    if pairs.is_empty() {
+        return Ok(pairs);
+    }
+    let closure = shortest_paths(matrix);
+    for (i, j) in pairs.iter().copied() {
+        matrix[i][j] = closure[i][j];
+    }
+    if let Some((i, j)) = unreachable(matrix).first() {
+        return Err(format!("destination {j} cannot be reached from destination {i}, even through other destinations"));
+    }
+    Ok(pairs)
+}
@@ -0,0 +1,62 @@
+//! This module provides the named generation presets, which bundle realistic
+//! combinations of the generation parameters (size of the area, number and spread
+//! of the clusters, optional waypoints, kind of travel costs, snapping). The presets
+//! are maintained in the `presets.json` data file.
+//!
+//! The generator has no notion of outliers nor of service times, hence the presets
+//! do not set them. Neither do they choose the routing profiles, which depend on
+//! the routing server (see --profiles).
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+/// The generation parameters set by a preset
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    /// What the instances generated with this preset look like
+    pub description: String,
+    /// The radius (in kilometres) of the area covered by the instances
+    pub radius_km: f32,
+    /// The number of clusters
+    pub nb_centroids: usize,
+    /// The radius (in kilometres) of the clusters
+    pub cluster_radius_km: f32,
+    /// The fraction of the destinations which are optional waypoints
+    #[serde(default)]
+    pub optional_fraction: f64,
+    /// Whether the travel costs are durations rather than distances
+    #[serde(default)]
+    pub duration: bool,
+    /// Whether all destinations are snapped to routable points
+    #[serde(default)]
+    pub force_routable: bool,
+    /// The maximum distance (in metres) by which a destination may be snapped
+    #[serde(default)]
+    pub max_snap_distance: Option<f32>,
+}
+
+/// Returns all the presets by name
+pub fn presets() -> BTreeMap<String, Preset> {
+    serde_json::from_str(include_str!("presets.json")).expect("the bundled presets are invalid")
+}
+
+/// Returns the preset having the given name
+pub fn preset(name: &str) -> Preset {
+    presets().remove(name).expect("unknown preset")
+}
+
+/// Parses the name of a preset, the error lists the available presets
+pub fn parse(name: &str) -> Result<String, String> {
+    let presets = presets();
+    if presets.contains_key(name) {
+        Ok(name.to_string())
+    } else {
+        let available = presets.iter()
+            .map(|(name, p)| format!("  {name}: {}", p.description))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Err(format!("unknown preset {name}, the available presets are:\n{available}"))
+    }
+}
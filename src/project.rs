@@ -0,0 +1,60 @@
+//! This module implements the project layout convention. A project is a directory
+//! that holds all the artifacts of one experiment under conventional names, so that
+//! the commands can find their inputs and outputs without repeating their paths:
+//! - `instance.json` the instance (written by generate, read by solve and visualize)
+//! - `solution.json` the best solution (written by solve, read by visualize)
+//! - `map.html`      the visualisation of the instance and solution
+//! - `log.jsonl`     one json line per command which has been run in the project
+
+use std::{fs::{self, OpenOptions}, io::Write, path::PathBuf, time::{SystemTime, UNIX_EPOCH}};
+
+use serde_json::Value;
+
+/// A directory comprising the artifacts of one experiment
+#[derive(Debug, Clone)]
+pub struct Project {
+    dir: PathBuf,
+}
+
+impl Project {
+    /// Opens the project stored in the given directory (creating it if needed)
+    pub fn open(dir: &str) -> Self {
+        fs::create_dir_all(dir).unwrap();
+        Self { dir: PathBuf::from(dir) }
+    }
+
+    /// The path of the instance of this project
+    pub fn instance(&self) -> String {
+        self.path("instance.json")
+    }
+
+    /// The path of the solution of this project
+    pub fn solution(&self) -> String {
+        self.path("solution.json")
+    }
+
+    /// The path of the visualisation of this project
+    pub fn map(&self) -> String {
+        self.path("map.html")
+    }
+
+    /// The path of the log of this project
+    pub fn log(&self) -> String {
+        self.path("log.jsonl")
+    }
+
+    /// Appends the results of a command to the log of this project
+    pub fn log_command(&self, command: &str, results: Value) {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let mut entry = serde_json::json!({ "command": command, "time": time });
+        if let (Some(entry), Value::Object(results)) = (entry.as_object_mut(), results) {
+            entry.extend(results);
+        }
+        let mut log = OpenOptions::new().create(true).append(true).open(self.log()).unwrap();
+        writeln!(log, "{}", serde_json::to_string(&entry).unwrap()).unwrap();
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.dir.join(name).to_string_lossy().into_owned()
+    }
+}
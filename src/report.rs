@@ -0,0 +1,200 @@
+//! This module defines how the results of the various commands are presented.
+//! Each command records its results in a report which is printed once the command
+//! has completed, in one of the following formats:
+//! - human: lines of free form text, where consecutive rows (label and value)
+//!   are laid out as an aligned table (colored on terminals unless NO_COLOR is set),
+//! - json: one single json document meant to be consumed by other programs,
+//! - porcelain: one `key<TAB>value` line per result; the keys are the same as in
+//!   the json document and are guaranteed to remain stable across versions,
+//! - quiet: nothing at all.
+//!
+//! The report is printed on the standard output, unless the command wrote its
+//! output (an instance, a solution, ...) there: it then goes to the standard error
+//! so that the output can be piped into another command.
+
+use std::{fmt::Display, io::IsTerminal};
+
+use comfy_table::{presets::UTF8_FULL_CONDENSED, Cell, Color, Table};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{files, i18n::{Lang, Messages}};
+
+/// The format in which a report is printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Human,
+    Json,
+    Porcelain,
+    Quiet,
+}
+
+/// One line of the text printed for humans
+#[derive(Debug, Clone)]
+enum Line {
+    /// Free form text
+    Text(String),
+    /// A row of a table: a label and a value (colored if it is a boolean)
+    Row(String, String, Option<bool>),
+}
+
+/// The results of a command
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The format in which the report is printed
+    format: Format,
+    /// The text printed for humans
+    lines: Vec<Line>,
+    /// The fields of the json document
+    fields: Map<String, Value>,
+    /// The language of the text shown to humans
+    lang: Lang,
+    /// The messages used to write the text shown to humans
+    messages: Messages,
+}
+
+impl Report {
+    /// Creates an empty report whose text is written in the given language
+    pub fn new(format: Format, lang: Lang) -> Self {
+        Self { format, lines: vec![], fields: Map::new(), lang, messages: Messages::new(lang) }
+    }
+
+    /// Returns the format in which this report is printed
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Returns the language of the text shown to humans
+    pub fn lang(&self) -> Lang {
+        self.lang
+    }
+
+    /// Returns the messages in the language of this report
+    pub fn messages(&self) -> &Messages {
+        &self.messages
+    }
+
+    /// Records a result under the given key, the text shown to humans is the
+    /// message having the given id (in which the value is substituted, booleans
+    /// being translated as well)
+    pub fn translated<T: Serialize + Display>(&mut self, key: &str, value: T, id: &str) {
+        let line = match serde_json::to_value(&value).unwrap().as_bool() {
+            Some(b) => self.messages.format(id, &[("value", &self.messages.boolean(b))]),
+            None => self.messages.format(id, &[("value", &value)]),
+        };
+        self.field(key, value, line);
+    }
+
+    /// Records a result under the given key, which is shown to humans as a row of
+    /// a table: its label is the message having the given id, and its value `text`
+    pub fn row<T: Serialize>(&mut self, key: &str, value: T, text: impl Into<String>, id: &str) {
+        let label = self.messages.label(id);
+        let json = serde_json::to_value(value).unwrap();
+        self.lines.push(Line::Row(label, text.into(), json.as_bool()));
+        self.fields.insert(key.to_string(), json);
+    }
+
+    /// Records a result under the given key, `line` is the text shown to humans
+    pub fn field<T: Serialize>(&mut self, key: &str, value: T, line: impl Into<String>) {
+        self.json(key, value);
+        self.line(line);
+    }
+
+    /// Records a result which does not appear in the text shown to humans
+    pub fn json<T: Serialize>(&mut self, key: &str, value: T) {
+        self.fields.insert(key.to_string(), serde_json::to_value(value).unwrap());
+    }
+
+    /// Records a line of text which is only shown to humans
+    pub fn line(&mut self, line: impl Into<String>) {
+        self.lines.push(Line::Text(line.into()));
+    }
+
+    /// Returns the json document of this report
+    pub fn document(&self) -> Value {
+        Value::Object(self.fields.clone())
+    }
+
+    /// Prints the report on the standard output (or on the standard error when the
+    /// standard output is used by the output of the command)
+    pub fn print(&self) {
+        let to_stderr = files::stdout_taken();
+        let lines = match self.format {
+            Format::Human => {
+                let terminal = if to_stderr { std::io::stderr().is_terminal() } else { std::io::stdout().is_terminal() };
+                self.human(terminal && std::env::var_os("NO_COLOR").is_none())
+            },
+            Format::Json => vec![serde_json::to_string(&self.fields).unwrap()],
+            Format::Porcelain => {
+                let mut lines = vec![];
+                for (key, value) in self.fields.iter() {
+                    Self::porcelain(key, value, &mut lines);
+                }
+                lines
+            },
+            Format::Quiet => vec![],
+        };
+        for line in lines {
+            if to_stderr {
+                eprintln!("{line}");
+            } else {
+                println!("{line}");
+            }
+        }
+    }
+
+    /// Returns the text shown to humans, where the consecutive rows are rendered
+    /// as tables (whose booleans are colored if `color` is set)
+    fn human(&self, color: bool) -> Vec<String> {
+        let mut text = vec![];
+        let mut table: Option<Table> = None;
+        for line in self.lines.iter() {
+            match line {
+                Line::Text(t) => {
+                    text.extend(table.take().map(|t| t.to_string()));
+                    text.push(t.clone());
+                },
+                Line::Row(label, value, flag) => {
+                    let mut cell = Cell::new(value);
+                    if let Some(ok) = flag.filter(|_| color) {
+                        cell = cell.fg(if ok { Color::Green } else { Color::Red });
+                    }
+                    table.get_or_insert_with(|| {
+                        let mut t = Table::new();
+                        t.load_preset(UTF8_FULL_CONDENSED);
+                        t
+                    }).add_row(vec![Cell::new(label), cell]);
+                },
+            }
+        }
+        text.extend(table.map(|t| t.to_string()));
+        text
+    }
+
+    /// Appends one porcelain line per scalar value. Nested objects are flattened
+    /// with dotted keys, arrays are printed as space separated values.
+    fn porcelain(key: &str, value: &Value, lines: &mut Vec<String>) {
+        match value {
+            Value::Object(fields) => {
+                for (k, v) in fields.iter() {
+                    Self::porcelain(&format!("{key}.{k}"), v, lines);
+                }
+            },
+            Value::Array(items) => {
+                let items = items.iter().map(Self::scalar).collect::<Vec<_>>();
+                lines.push(format!("{key}\t{}", items.join(" ")));
+            },
+            _ => lines.push(format!("{key}\t{}", Self::scalar(value))),
+        }
+    }
+
+    /// Returns the text of a value on one single line
+    fn scalar(value: &Value) -> String {
+        match value {
+            Value::Null => String::new(),
+            Value::String(s) => s.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n"),
+            other => other.to_string(),
+        }
+    }
+}
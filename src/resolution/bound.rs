@@ -0,0 +1,156 @@
+//! This module computes the Held-Karp lower bound of an instance. That bound is
+//! the best 1-tree bound which is obtained by a lagrangian relaxation of the degree
+//! constraints of the TSP, and it is approached by subgradient ascent.
+//!
+//! Because the travel cost matrices are generally not symmetric, the bound is
+//! computed on the symmetric matrix where each edge costs the cheapest of both
+//! directions. This remains a valid lower bound for the asymmetric instance.
+//!
+//! Both the bound and the heuristic tour visit every destination: they are only
+//! meaningful for plain TSP instances, which have neither clusters to pick one
+//! destination from (GTSP) nor optional destinations.
+
+use std::time::Duration;
+
+use clap::Args;
+
+use crate::{solution::Solution, report::Report, files};
+
+use super::local_search::{LocalSearch, nearest_neighbour, tour_cost};
+
+/// This command computes a certified lower bound on the length of the optimal tour
+/// and reports it along with a heuristic tour, so as to give the optimality gap of
+/// that tour without running the exact solver to completion.
+#[derive(Debug, Args)]
+pub struct Bound {
+    /// The path to the instance file (`-` for the standard input)
+    #[clap(short, long)]
+    pub instance: String,
+    /// The maximum number of subgradient iterations
+    #[clap(short='n', long, default_value="1000")]
+    pub iterations: usize,
+    /// The time (in seconds) spent improving the heuristic tour
+    #[clap(short, long, default_value="5")]
+    pub timeout: u64,
+}
+
+impl Bound {
+    /// Executes this command
+    pub async fn execute(&self, report: &mut Report) {
+        let instance = files::read_instance(&self.instance);
+        if !instance.variant.is_tsp() || (0..instance.len()).any(|i| instance.is_optional(i)) {
+            clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!(
+                "{}: the bound is only valid when all destinations must be visited, which is not the case \
+                with the gtsp variant or optional destinations\n", self.instance))
+                .exit();
+        }
+
+        let costs = instance.costs();
+        let mut tour = nearest_neighbour(&costs);
+        let search = LocalSearch { time_limit: Duration::from_secs(self.timeout), ..LocalSearch::default() };
+        let upper = search.improve(&costs, &mut tour);
+        let lower = held_karp(&costs, upper, self.iterations);
+
+        let gap = if upper > 0.0 { 100.0 * (upper - lower) / upper } else { 0.0 };
+        let lower = lower / 1000.0; // en kilometres
+        let upper = upper / 1000.0; // en kilometres
+        report.json("instance", &self.instance);
+        report.translated("lower_bound", lower, "lower-bound");
+        report.translated("best_value", upper, "best-value");
+        let line = report.messages().format("gap", &[("value", &format!("{gap:.2}"))]);
+        report.field("gap", gap, line);
+
+        let solution = Solution{tour};
+        let line = report.messages().format("solution", &[("value", &solution.to_text())]);
+        report.field("solution", &solution.tour, line);
+    }
+}
+
+/// Computes the Held-Karp lower bound of the given instance. The `upper` bound (cost
+/// of a known tour) is used to size the steps of the subgradient ascent.
+pub fn held_karp(distances: &[Vec<f32>], upper: f32, iterations: usize) -> f32 {
+    let n = distances.len();
+    if n < 3 {
+        let tour = (0..n).collect::<Vec<_>>();
+        return tour_cost(distances, &tour);
+    }
+    let costs = (0..n)
+        .map(|i| (0..n).map(|j| distances[i][j].min(distances[j][i]) as f64).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let mut pi = vec![0.0_f64; n];
+    let mut best = f64::NEG_INFINITY;
+    let mut lambda = 2.0_f64;
+    let mut stale = 0;
+    for _ in 0..iterations {
+        let (length, degrees) = one_tree(&costs, &pi);
+        let bound = length - 2.0 * pi.iter().sum::<f64>();
+        if bound > best + 1e-9 {
+            best = bound;
+            stale = 0;
+        } else {
+            stale += 1;
+            if stale >= 10 {
+                lambda /= 2.0;
+                stale = 0;
+            }
+        }
+
+        let norm = degrees.iter().map(|d| (*d as f64 - 2.0).powi(2)).sum::<f64>();
+        if norm == 0.0 || lambda < 1e-6 {
+            break; // the 1-tree is a tour (hence optimal), or the ascent has converged
+        }
+        let step = lambda * (upper as f64 - bound).max(0.0) / norm;
+        if step == 0.0 {
+            break;
+        }
+        pi.iter_mut().zip(degrees.iter())
+            .for_each(|(p, d)| *p += step * (*d as f64 - 2.0));
+    }
+    best.min(upper as f64) as f32
+}
+
+/// Returns the length of the minimum 1-tree for the costs penalized by pi, along
+/// with the degree of each node in that 1-tree. The 1-tree is a spanning tree of
+/// the nodes 1..n plus the two cheapest edges incident to node 0.
+fn one_tree(costs: &[Vec<f64>], pi: &[f64]) -> (f64, Vec<usize>) {
+    let n = costs.len();
+    let cost = |i: usize, j: usize| costs[i][j] + pi[i] + pi[j];
+    let mut degrees = vec![0; n];
+    let mut length = 0.0;
+
+    // prim on the nodes 1..n
+    let mut in_tree = vec![false; n];
+    let mut dist = vec![f64::INFINITY; n];
+    let mut parent = vec![1; n];
+    dist[1] = 0.0;
+    for _ in 1..n {
+        let u = (1..n)
+            .filter(|v| !in_tree[*v])
+            .min_by(|a, b| dist[*a].total_cmp(&dist[*b]))
+            .unwrap();
+        in_tree[u] = true;
+        if u != 1 {
+            length += dist[u];
+            degrees[u] += 1;
+            degrees[parent[u]] += 1;
+        }
+        for v in (1..n).filter(|v| !in_tree[*v]) {
+            let c = cost(u, v);
+            if c < dist[v] {
+                dist[v] = c;
+                parent[v] = u;
+            }
+        }
+    }
+
+    // the two cheapest edges incident to node 0
+    let mut edges = (1..n).map(|v| (cost(0, v), v)).collect::<Vec<_>>();
+    edges.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+    for (c, v) in edges.iter().take(2) {
+        length += c;
+        degrees[0] += 1;
+        degrees[*v] += 1;
+    }
+    (length, degrees)
+}
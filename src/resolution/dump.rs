@@ -0,0 +1,114 @@
+//! This module exports the relaxed decision diagram compiled from the root of an
+//! instance in the Graphviz (dot) format. The diagram is compiled top down, layer
+//! by layer, in the very same way as the solver does it: whenever a layer grows
+//! wider than the maximum width, its worst ranked states are merged into one single
+//! node. The merged nodes are highlighted so that the effect of the relaxation can
+//! be shown on small instances.
+
+use std::{cmp::Ordering, collections::HashMap, fmt::Write};
+
+use ddo::{Decision, Problem, Relaxation, StateRanking};
+
+use super::{matrix::Cost, model::{TspModel, TspRanking, TspState}};
+
+/// One node of the exported diagram
+struct Node {
+    state: TspState,
+    /// The value of the longest path from the root to this node
+    value: isize,
+    /// True iff this node results from the merger of several states
+    merged: bool,
+}
+
+/// One arc of the exported diagram
+struct Arc {
+    from: usize,
+    to: usize,
+    decision: Decision,
+    cost: isize,
+}
+
+/// Compiles the relaxed decision diagram of the given width from the root of the
+/// problem, and returns its Graphviz representation
+pub fn relaxed_dd<C: Cost>(
+    problem: &TspModel<C>,
+    relaxation: &dyn Relaxation<State = TspState>,
+    ranking: &TspRanking<C>,
+    width: usize,
+) -> String {
+    let width = width.max(1);
+    let mut nodes = vec![Node { state: problem.initial_state(), value: problem.initial_value(), merged: false }];
+    let mut arcs: Vec<Arc> = vec![];
+    let mut layer = vec![0];
+
+    while let Some(var) = problem.next_variable(&mut layer.iter().map(|i| &nodes[*i].state)) {
+        // expand all the nodes of the current layer
+        let mut next: HashMap<TspState, Vec<(usize, Decision, isize)>> = HashMap::new();
+        for &i in layer.iter() {
+            let state = nodes[i].state;
+            let mut decisions = vec![];
+            problem.for_each_in_domain(var, &state, &mut |d: Decision| decisions.push(d));
+            for decision in decisions {
+                let cost = problem.transition_cost(&state, decision);
+                next.entry(problem.transition(&state, decision)).or_default().push((i, decision, cost));
+            }
+        }
+        if next.is_empty() {
+            break;
+        }
+
+        // keep the best ranked states, and merge all the others
+        let mut states = next.into_iter().collect::<Vec<_>>();
+        states.sort_by(|a, b| ranking.compare(&b.0, &a.0).then_with(|| by_label(&a.0, &b.0)));
+        let mut merged_state = None;
+        if states.len() > width {
+            let squashed = states.split_off(width - 1);
+            let merged = relaxation.merge(&mut squashed.iter().map(|(s, _)| s));
+            let mut inbound = vec![];
+            for (dest, edges) in squashed.iter() {
+                for &(from, decision, cost) in edges {
+                    let source = &nodes[from].state;
+                    inbound.push((from, decision, relaxation.relax(source, dest, &merged, decision, cost)));
+                }
+            }
+            match states.iter_mut().find(|(s, _)| *s == merged) {
+                Some((_, edges)) => edges.extend(inbound),
+                None => states.push((merged, inbound)),
+            }
+            merged_state = Some(merged);
+        }
+
+        layer.clear();
+        for (state, edges) in states {
+            let id = nodes.len();
+            let value = edges.iter().map(|(from, _, cost)| nodes[*from].value.saturating_add(*cost)).max().unwrap_or(0);
+            let merged = merged_state == Some(state);
+            nodes.push(Node { state, value, merged });
+            arcs.extend(edges.into_iter().map(|(from, decision, cost)| Arc { from, to: id, decision, cost }));
+            layer.push(id);
+        }
+    }
+
+    to_dot(problem, &nodes, &arcs)
+}
+
+/// The states of a layer are sorted deterministically even when they are ranked
+/// equally, so that two exports of the same instance are identical
+fn by_label(a: &TspState, b: &TspState) -> Ordering {
+    format!("{a}").cmp(&format!("{b}"))
+}
+
+/// Returns the Graphviz representation of the given diagram
+fn to_dot<C: Cost>(problem: &TspModel<C>, nodes: &[Node], arcs: &[Arc]) -> String {
+    let mut dot = String::from("digraph relaxed_dd {\n    rankdir=TB;\n    node [shape=box, fontsize=10];\n");
+    for (i, node) in nodes.iter().enumerate() {
+        let style = if node.merged { ", style=filled, fillcolor=\"#f4cccc\"" } else { "" };
+        writeln!(dot, "    n{i} [label=\"{}\\nvalue {:.2}\"{style}];", node.state, problem.travel_cost(node.value)).unwrap();
+    }
+    for arc in arcs {
+        writeln!(dot, "    n{} -> n{} [label=\"{} ({:.2})\"];",
+            arc.from, arc.to, arc.decision.value, problem.travel_cost(arc.cost)).unwrap();
+    }
+    dot.push_str("}\n");
+    dot
+}
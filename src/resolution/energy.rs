@@ -0,0 +1,60 @@
+//! This module implements the energy consumption model of an electric vehicle. It
+//! turns the distances between the destinations (and their elevations) into the
+//! energy which is needed to travel from one destination to the other.
+
+use clap::Args;
+
+/// The standard gravity (m/s²)
+const GRAVITY: f32 = 9.81;
+/// The density of the air (kg/m³)
+const AIR_DENSITY: f32 = 1.225;
+/// The number of joules in one watt-hour
+const JOULES_PER_WH: f32 = 3600.0;
+
+/// The physical parameters of the vehicle
+#[derive(Debug, Clone, Args)]
+pub struct EnergyModel {
+    /// The mass of the vehicle, including its load (kg)
+    #[clap(long, default_value="1800")]
+    pub mass: f32,
+    /// The drag area of the vehicle, that is its drag coefficient times its frontal area (m²)
+    #[clap(long, default_value="0.6")]
+    pub drag_area: f32,
+    /// The rolling resistance coefficient of the tyres
+    #[clap(long, default_value="0.01")]
+    pub rolling_resistance: f32,
+    /// The fraction of the potential energy which is recovered when going downhill
+    #[clap(long, default_value="0.6")]
+    pub regen: f32,
+    /// The average speed of the vehicle (km/h), which determines the drag losses
+    #[clap(long, default_value="50")]
+    pub speed: f32,
+}
+
+impl EnergyModel {
+    /// Returns the energy (in Wh) needed to travel each of the given distances (in
+    /// metres) between destinations having the given elevations (in metres). The
+    /// energy recovered downhill can only make up for the losses of the same leg:
+    /// a leg never charges the battery (its energy is never negative).
+    pub fn matrix(&self, distances: &[Vec<f32>], elevations: &[f32]) -> Vec<Vec<f32>> {
+        distances.iter().enumerate()
+            .map(|(i, row)| row.iter().enumerate()
+                .map(|(j, d)| self.energy(*d, elevations[j] - elevations[i]))
+                .collect())
+            .collect()
+    }
+
+    /// Returns the energy (in Wh) needed to travel the given distance with the
+    /// given difference of elevation
+    pub fn energy(&self, distance: f32, climb: f32) -> f32 {
+        if !distance.is_finite() {
+            return distance;
+        }
+        let speed = self.speed / 3.6; // in m/s
+        let resistance = self.mass * GRAVITY * self.rolling_resistance
+            + 0.5 * AIR_DENSITY * self.drag_area * speed * speed;
+        let potential = self.mass * GRAVITY * climb;
+        let potential = if potential > 0.0 { potential } else { self.regen * potential };
+        (resistance * distance + potential).max(0.0) / JOULES_PER_WH
+    }
+}
@@ -0,0 +1,168 @@
+//! This module provides a simple local search which is used to polish the tours
+//! found by the other resolution methods. A tour is a sequence of destination
+//! identifiers which starts at the depot (0) and implicitly returns to it.
+
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+
+use crate::solution::Solution;
+
+use super::matrix::CostMatrix;
+
+/// The kinds of moves the local search is allowed to perform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Move {
+    /// Reverses a portion of the tour
+    TwoOpt,
+    /// Moves a chain of up to three consecutive destinations elsewhere in the tour
+    OrOpt,
+}
+
+/// Makes the given tour deterministic among its equivalent forms: it is rotated so
+/// as to start at the depot, and it takes the direction of its canonical form (see
+/// `Solution::canonicalize`) when both directions have the same cost (which happens
+/// with symmetric travel costs). Hence, equally good tours found by different runs
+/// are reported the same way.
+pub fn normalize(distances: &[Vec<f32>], tour: &mut [usize]) {
+    if let Some(depot) = tour.iter().position(|x| *x == 0) {
+        tour.rotate_left(depot);
+    }
+    let canonical = Solution { tour: tour.to_vec() }.canonicalize().tour;
+    if canonical.len() != tour.len() {
+        return; // the tour returns explicitly to the depot: it is not a permutation
+    }
+    let (cost, canonical_cost) = (tour_cost(distances, tour), tour_cost(distances, &canonical));
+    // an infinite cost means the tour travels along a forbidden edge (or breaks a
+    // required one): such a tour is never taken as the same cost as another one
+    let same_cost = cost.is_finite() && canonical_cost.is_finite()
+        && (cost - canonical_cost).abs() <= 1e-6 * cost.abs().max(canonical_cost.abs());
+    if same_cost {
+        tour.copy_from_slice(&canonical);
+    }
+}
+
+/// Returns the cost of travelling along the (closed) tour
+pub fn tour_cost(distances: &[Vec<f32>], tour: &[usize]) -> f32 {
+    if tour.is_empty() {
+        return 0.0;
+    }
+    tour.windows(2)
+        .map(|w| distances[w[0]][w[1]])
+        .sum::<f32>()
+        + distances[tour[tour.len() - 1]][tour[0]]
+}
+
+/// Returns the tour obtained by always travelling to the closest destination which
+/// has not been visited yet (starting from the depot)
+pub fn nearest_neighbour(distances: &[Vec<f32>]) -> Vec<usize> {
+    let costs = CostMatrix::new(distances);
+    let mut candidates = costs.all_candidates();
+    let mut tour = Vec::with_capacity(distances.len());
+    let mut current = (!distances.is_empty()).then_some(0);
+    while let Some(city) = current {
+        CostMatrix::<f32>::remove_candidate(&mut candidates, city);
+        tour.push(city);
+        current = costs.nearest_in_row(city, &candidates);
+    }
+    tour
+}
+
+/// A first improvement local search bounded in time
+#[derive(Debug, Clone)]
+pub struct LocalSearch {
+    /// The moves that are tried (in that order)
+    pub moves: Vec<Move>,
+    /// The maximum amount of time spent improving a tour
+    pub time_limit: Duration,
+}
+
+impl Default for LocalSearch {
+    fn default() -> Self {
+        Self { moves: vec![Move::TwoOpt, Move::OrOpt], time_limit: Duration::from_secs(10) }
+    }
+}
+
+impl LocalSearch {
+    /// Improves the given tour in place until no move yields an improvement or the
+    /// time limit is reached. It returns the cost of the improved tour.
+    pub fn improve(&self, distances: &[Vec<f32>], tour: &mut [usize]) -> f32 {
+        let start = Instant::now();
+        let mut improved = true;
+        while improved && start.elapsed() < self.time_limit {
+            improved = false;
+            for mv in self.moves.iter().copied() {
+                improved |= match mv {
+                    Move::TwoOpt => Self::two_opt(distances, tour),
+                    Move::OrOpt  => Self::or_opt(distances, tour),
+                };
+            }
+        }
+        tour_cost(distances, tour)
+    }
+
+    /// Applies the first improving 2-opt move. The matrix needs not be symmetric:
+    /// the cost of the reversed portion is accounted for.
+    fn two_opt(distances: &[Vec<f32>], tour: &mut [usize]) -> bool {
+        let n = tour.len();
+        for i in 0..n.saturating_sub(2) {
+            for j in (i + 2)..n {
+                let a = tour[i];
+                let b = tour[i + 1];
+                let c = tour[j];
+                let d = tour[(j + 1) % n];
+                if a == d {
+                    continue;
+                }
+                let forward  = (i + 1..j).map(|k| distances[tour[k]][tour[k + 1]]).sum::<f32>();
+                let backward = (i + 1..j).map(|k| distances[tour[k + 1]][tour[k]]).sum::<f32>();
+                let before = distances[a][b] + forward  + distances[c][d];
+                let after  = distances[a][c] + backward + distances[b][d];
+                if after + f32::EPSILON < before {
+                    tour[i + 1..=j].reverse();
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Applies the first improving or-opt move (the depot never moves)
+    fn or_opt(distances: &[Vec<f32>], tour: &mut [usize]) -> bool {
+        let n = tour.len();
+        for len in 1..=3 {
+            for s in 1..n {
+                let e = s + len - 1; // last position of the moved chain
+                if e >= n {
+                    break;
+                }
+                let prev = tour[s - 1];
+                let next = tour[(e + 1) % n];
+                let first = tour[s];
+                let last = tour[e];
+                // the costs are compared without subtraction, which keeps the comparison
+                // meaningful when some edges are forbidden (have an infinite cost)
+                let removed = distances[prev][first] + distances[last][next];
+                for p in 0..n {
+                    // the chain is inserted between positions p and p+1
+                    if p + 1 >= s && p <= e {
+                        continue;
+                    }
+                    let u = tour[p];
+                    let v = tour[(p + 1) % n];
+                    let before = removed + distances[u][v];
+                    let after = distances[prev][next] + distances[u][first] + distances[last][v];
+                    if after + f32::EPSILON < before {
+                        let chain = tour[s..=e].to_vec();
+                        let mut rest = tour[..s].iter().chain(tour[e + 1..].iter()).copied().collect::<Vec<_>>();
+                        let at = if p < s { p + 1 } else { p + 1 - len };
+                        rest.splice(at..at, chain);
+                        tour.copy_from_slice(&rest);
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+}
@@ -0,0 +1,241 @@
+//! This module provides a dense representation of the travel cost matrix that is
+//! tailored to the hot loops of the solver. All rows are stored in one flat buffer
+//! made of aligned lanes, so that scanning a row (e.g. to find the cheapest edge
+//! leaving a city) boils down to a few fixed size chunks which the compiler is able
+//! to vectorize. These scans are used to compute the cheapest outgoing edges that
+//! the bounds of the solver rely on (see `min_in_row`), and to pick the next city
+//! of the greedy construction of a tour (see `nearest_in_row`).
+//!
+//! The matrix either holds the raw floating point costs of the instance, or a fixed
+//! point version thereof (see `CostMatrix::quantize`) which spares the solver a float
+//! multiplication and rounding on each transition.
+
+use std::fmt::Debug;
+
+use smallbitset::Set64;
+
+/// The number of costs that are packed together in one lane
+pub const LANES: usize = 8;
+
+/// The scale by which floating point costs are multiplied before being rounded to
+/// the integer values manipulated by the solver
+pub const FLOAT_SCALE: f32 = 100_000.0;
+
+/// The largest number of decimals that can be requested for fixed point costs
+pub const MAX_PRECISION: u32 = 30;
+
+/// Returns the largest precision (number of decimals) with which all the finite
+/// given costs can be represented as 32 bits fixed point values, or None when some
+/// cost is too large even without decimals
+pub fn max_precision(distances: &[Vec<f32>]) -> Option<u32> {
+    let largest = distances.iter().flatten().copied()
+        .filter(|cost| cost.is_finite())
+        .map(f32::abs)
+        .fold(0.0, f32::max);
+    (0..=MAX_PRECISION)
+        .take_while(|p| (largest * 10_f32.powi(*p as i32)).round() < i32::MAX as f32)
+        .last()
+}
+
+/// The type of the costs that can be stored in a cost matrix
+pub trait Cost: Copy + PartialOrd + Debug + Send + Sync {
+    /// A value which is larger than any actual cost (used for padding)
+    const INFINITY: Self;
+    /// Returns the smallest of the two costs
+    fn min(self, other: Self) -> Self;
+    /// Returns the integer value of this cost as it is manipulated by the solver
+    fn fixed(self) -> isize;
+}
+
+impl Cost for f32 {
+    const INFINITY: Self = f32::INFINITY;
+
+    #[inline]
+    fn min(self, other: Self) -> Self {
+        f32::min(self, other)
+    }
+    #[inline]
+    fn fixed(self) -> isize {
+        // in double precision: the scaled costs of a few km exceed the mantissa of a f32
+        (self as f64 * FLOAT_SCALE as f64).round() as isize
+    }
+}
+
+impl Cost for i32 {
+    const INFINITY: Self = i32::MAX;
+
+    #[inline]
+    fn min(self, other: Self) -> Self {
+        Ord::min(self, other)
+    }
+    #[inline]
+    fn fixed(self) -> isize {
+        self as isize
+    }
+}
+
+/// A chunk of LANES consecutive costs from one row of the matrix
+#[derive(Debug, Clone, Copy)]
+#[repr(C, align(32))]
+struct Lane<C>([C; LANES]);
+
+/// A square cost matrix stored row by row in a flat, 32 bytes aligned buffer.
+/// Each row is padded with infinite costs up to a multiple of LANES so that no
+/// scan ever needs to deal with a remainder.
+#[derive(Debug, Clone)]
+pub struct CostMatrix<C: Cost = f32> {
+    /// The number of destinations
+    n: usize,
+    /// The number of lanes used to store one row of the matrix
+    lanes_per_row: usize,
+    /// The factor by which the original costs are multiplied in the solver
+    scale: f32,
+    /// The actual costs
+    data: Vec<Lane<C>>,
+}
+
+impl CostMatrix<f32> {
+    /// Creates a flat cost matrix from the nested representation used in the instances
+    pub fn new(distances: &[Vec<f32>]) -> Self {
+        let n = distances.len();
+        let lanes_per_row = n.div_ceil(LANES);
+        let mut data = vec![Lane([f32::INFINITY; LANES]); n * lanes_per_row];
+        for (i, row) in distances.iter().enumerate() {
+            for (j, cost) in row.iter().copied().enumerate() {
+                data[i * lanes_per_row + j / LANES].0[j % LANES] = cost;
+            }
+        }
+        Self { n, lanes_per_row, scale: FLOAT_SCALE, data }
+    }
+
+    /// Converts this matrix into a fixed point matrix where each cost is rounded
+    /// to the given number of decimals.
+    ///
+    /// # Panics
+    /// When some cost is too large to be represented on 32 bits with that precision
+    /// (see `max_precision`).
+    pub fn quantize(&self, precision: u32) -> CostMatrix<i32> {
+        let scale = 10_f32.powi(precision as i32);
+        let data = self.data.iter()
+            .map(|lane| Lane(lane.0.map(|cost| {
+                if cost.is_infinite() {
+                    return i32::INFINITY;
+                }
+                let fixed = (cost * scale).round();
+                assert!(fixed < i32::MAX as f32 && fixed > i32::MIN as f32,
+                    "cost {cost} cannot be represented on 32 bits with a precision of {precision} decimals");
+                fixed as i32
+            })))
+            .collect();
+        CostMatrix { n: self.n, lanes_per_row: self.lanes_per_row, scale, data }
+    }
+}
+
+impl <C: Cost> CostMatrix<C> {
+    /// Returns the number of destinations in the matrix
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    /// Returns the factor by which the solver values must be divided to get back
+    /// to the units of the instance
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Returns the cost of travelling from i to j
+    #[inline]
+    pub fn get(&self, i: usize, j: usize) -> C {
+        self.data[i * self.lanes_per_row + j / LANES].0[j % LANES]
+    }
+
+    /// Returns the cheapest cost of an edge leaving i towards one of the destinations
+    /// in `to` (infinite when `to` is empty)
+    pub fn min_in_row(&self, i: usize, to: Set64) -> C {
+        let mask = Self::mask(to);
+        self.row(i).iter().enumerate()
+            .map(|(l, lane)| Self::lane_min(lane, (mask >> (l * LANES)) as u8))
+            .fold(C::INFINITY, C::min)
+    }
+
+    /// Returns the destination among the candidates which is the cheapest to reach
+    /// from i. The candidates are given as one byte of LANES bits per lane of the row
+    /// (see `all_candidates`). When all candidates are out of reach, the first one is
+    /// returned; and None is returned when there is no candidate at all.
+    pub fn nearest_in_row(&self, i: usize, candidates: &[u8]) -> Option<usize> {
+        let mut best: Option<(usize, C)> = None;
+        for (l, (lane, bits)) in self.row(i).iter().zip(candidates.iter().copied()).enumerate() {
+            if bits == 0 {
+                continue;
+            }
+            let min = Self::lane_min(lane, bits);
+            if best.is_none_or(|(_, cost)| min < cost) {
+                best = Some((l, min));
+            }
+        }
+        let (l, min) = best?;
+        let bits = candidates[l];
+        let k = (0..LANES)
+            .find(|k| bits & (1 << k) != 0 && self.data[i * self.lanes_per_row + l].0[*k] == min)
+            .unwrap_or(bits.trailing_zeros() as usize);
+        Some(l * LANES + k)
+    }
+
+    /// Returns the candidates (see `nearest_in_row`) comprising all destinations
+    pub fn all_candidates(&self) -> Vec<u8> {
+        let mut candidates = vec![0_u8; self.lanes_per_row];
+        for j in 0..self.n {
+            candidates[j / LANES] |= 1 << (j % LANES);
+        }
+        candidates
+    }
+
+    /// Returns the cheapest cost of an edge leaving i towards any other destination
+    pub fn min_outgoing(&self, i: usize) -> C {
+        let mut others = Set64::empty();
+        for j in (0..self.n).filter(|j| *j != i) {
+            others = others.insert(j as u8);
+        }
+        self.min_in_row(i, others)
+    }
+
+    /// Removes the destination j from the given candidates (see `nearest_in_row`)
+    pub fn remove_candidate(candidates: &mut [u8], j: usize) {
+        candidates[j / LANES] &= !(1 << (j % LANES));
+    }
+
+    /// Returns the lanes that make up the i-th row
+    #[inline]
+    fn row(&self, i: usize) -> &[Lane<C>] {
+        &self.data[i * self.lanes_per_row..(i + 1) * self.lanes_per_row]
+    }
+
+    /// Turns a set of destinations into a plain bit mask
+    #[inline]
+    fn mask(set: Set64) -> u64 {
+        set.iter().fold(0_u64, |acc, j| acc | (1 << j))
+    }
+
+    /// Returns the minimum of the costs which are selected by the given bits
+    #[inline]
+    fn lane_min(lane: &Lane<C>, bits: u8) -> C {
+        if bits == 0 {
+            return C::INFINITY;
+        }
+        let mut selected = [C::INFINITY; LANES];
+        for (k, sel) in selected.iter_mut().enumerate() {
+            if bits & (1 << k) != 0 {
+                *sel = lane.0[k];
+            }
+        }
+        // pairwise reduction: three steps of independent mins
+        let mut width = LANES / 2;
+        while width > 0 {
+            for k in 0..width {
+                selected[k] = selected[k].min(selected[k + width]);
+            }
+            width /= 2;
+        }
+        selected[0]
+    }
+}
@@ -1,58 +1,598 @@
 //! This module provides the facilities to solve a tsp instance using branch and bound with mdd
 
-use std::{fs::File, io::BufReader, time::Duration};
+use std::{sync::Arc, time::{Duration, Instant}};
 
-use clap::Args;
-use ddo::{ParallelSolver, FixedWidth, TimeBudget, SimpleFrontier, MaxUB, Solver, Completion};
+use clap::{Args, ValueEnum, FromArgMatches};
+use ddo::{ParallelSolver, FixedWidth, TimeBudget, SimpleFrontier, MaxUB, Solver, Completion, Decision, Problem,
+    Relaxation, Cutoff, SubProblem, DefaultMDD, DecisionDiagram, CompilationInput, CompilationType};
+use osrm_client::Location;
 
-use self::model::{TspModel, TspRelax, TspRanking};
+use crate::{instance::Instance, solution::Solution, report::Report, files, project::Project, elevation, footprint::Footprint};
 
+use self::{energy::EnergyModel, model::{TspModel, TspState, TspRelax, TspRanking, Ranking, BranchingOrder, VisitOptional, MAX_DESTINATIONS}, matrix::Cost, local_search::{LocalSearch, nearest_neighbour}, stats::{Monitor, CountingMdd}, widening::{IncumbentFrontier, Phase}, trace::{Tracer, TracingFrontier, TracingRelax, TracingMdd}};
+
+mod matrix;
 mod model;
+mod bound;
+mod energy;
+mod polish;
+mod stats;
+mod widening;
+mod dump;
+mod trace;
+pub mod local_search;
+
+pub use bound::Bound;
+pub use polish::Polish;
+
+/// The ways an instance can be decomposed into smaller problems
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Decomposition {
+    /// Sequences the clusters first, then orders the destinations of each cluster
+    Cluster,
+}
+
+/// The algorithms which can be used to solve an instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Algorithm {
+    /// Branch and bound with decision diagrams, which proves the optimality of the
+    /// tour if it is given enough time
+    BranchAndBound,
+    /// Compiles one single restricted decision diagram of the given width and
+    /// returns its best tour: a fast heuristic
+    RestrictedDd,
+}
+
+/// The ways the branch and bound explores the nodes of its frontier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Execution {
+    /// Explores several nodes at once, with one thread per available cpu
+    Parallel,
+    /// Explores the nodes one at a time on a single thread: this spares the
+    /// synchronization overhead on small instances and makes runs reproducible
+    Sequential,
+}
+impl Execution {
+    /// The number of threads used by the solver
+    fn threads(self) -> usize {
+        match self {
+            Execution::Parallel   => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            Execution::Sequential => 1,
+        }
+    }
+}
+
+/// The quantity which is minimized by the tour
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Objective {
+    /// The travel cost of the instance (distance or duration)
+    Distance,
+    /// The energy consumed by an electric vehicle, which depends on the distances
+    /// and the elevations of the destinations (the latter requires --elevation-url)
+    Energy,
+}
 
-/// This command lets you generate an html file to visualize a given instance
-/// and an optional solution.
+/// This command solves an instance with branch and bound with mdd.
 #[derive(Debug, Args)]
 pub struct Solve {
-    /// The path to the instance file
-    #[clap(short, long)]
-    pub instance: String,
+    /// The path to the instance file (`-` for the standard input)
+    #[clap(short, long, required_unless_present="project")]
+    pub instance: Option<String>,
     /// max number of nodes in a layeer
     #[clap(short, long, default_value="100")]
     pub width: usize,
     /// timeout
     #[clap(short, long, default_value="60")]
     pub timeout: u64,
+    /// If present, the travel costs are converted once into 32 bits fixed point
+    /// values having this many decimals, and the solver works with these exact
+    /// integers instead of rounding floats on each transition
+    #[clap(short, long)]
+    pub precision: Option<u32>,
+    /// The algorithm used to solve the instance
+    #[clap(short, long, value_enum, default_value_t=Algorithm::BranchAndBound)]
+    pub algorithm: Algorithm,
+    /// Run the branch and bound several times with widths growing geometrically
+    /// from the given width, each run starting with the best tour found so far
+    #[clap(long)]
+    pub widening: bool,
+    /// Whether the branch and bound runs on all the cpus or on a single thread
+    #[clap(long, value_enum, default_value_t=Execution::Parallel)]
+    pub solver: Execution,
+    /// The order in which the successors of a node are explored
+    #[clap(short, long, value_enum, default_value_t=BranchingOrder::Nearest)]
+    pub branching: BranchingOrder,
+    /// The criterion used to rank the states (the best ones are kept when a decision
+    /// diagram is restricted or relaxed)
+    #[clap(short, long, value_enum, default_value_t=Ranking::Depth)]
+    pub ranking: Ranking,
+    /// Whether the destinations tagged optional are visited
+    #[clap(long, value_enum, default_value_t=VisitOptional::IfFree)]
+    pub visit_optional: VisitOptional,
+    /// If present, the instance is not solved as a whole but decomposed in smaller
+    /// problems whose solutions are stitched together and polished by local search
+    #[clap(long, value_enum)]
+    pub decompose: Option<Decomposition>,
+    /// If present, the routing profile (among those stored in the instance) whose
+    /// travel costs are minimized
+    #[clap(long)]
+    pub profile: Option<String>,
+    /// The quantity which is minimized
+    #[clap(long, value_enum, default_value_t=Objective::Distance)]
+    pub objective: Objective,
+    /// The URL of an elevation service (Open-Elevation api) which gives the
+    /// elevations of the destinations for the energy objective
+    #[clap(long, required_if_eq("objective", "energy"))]
+    pub elevation_url: Option<String>,
+    /// If present, the path where to write the relaxed decision diagram compiled
+    /// from the root of the instance (in the Graphviz format). This is only
+    /// readable for small instances and widths.
+    #[clap(long, conflicts_with="decompose")]
+    pub dump_dd: Option<String>,
+    /// Log the nodes selected, the states merged and the nodes pruned by the branch
+    /// and bound on the standard error (the search then runs on a single thread).
+    /// This is only readable for tiny instances.
+    #[clap(long)]
+    pub trace: bool,
+    #[clap(flatten)]
+    pub vehicle: EnergyModel,
+    #[clap(flatten)]
+    pub footprint: Footprint,
 
-    /// If present, the path where to write the output html
+    /// If present, the path where to write the solution (as a json document)
     #[clap(short, long)]
     pub output: Option<String>,
+    /// Overwrite the output file if it already exists
+    #[clap(long)]
+    pub force: bool,
+    /// If present, the directory of the project whose conventionally named files
+    /// are used as default inputs and outputs of this command
+    #[clap(short='P', long)]
+    pub project: Option<String>,
+}
+
+/// The result of solving an instance
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    /// True iff the tour is proven to be optimal
+    pub is_exact: bool,
+    /// The cost of the tour (in the units of the instance)
+    pub value: f32,
+    /// The tour, which starts at the depot (0) and implicitly returns to it
+    pub tour: Vec<usize>,
+    /// The phases of the iterative widening (empty when it is not used)
+    pub phases: Vec<Phase>,
+}
+
+impl Default for Solve {
+    /// The solver having the default value of all its command line options
+    fn default() -> Self {
+        let command = Self::augment_args(clap::Command::new("solve"));
+        Self::from_arg_matches(&command.get_matches_from(["solve", "--instance", files::STDIO])).unwrap()
+    }
 }
 
 impl Solve {
-    pub async fn execute(&self) {
-        let instance = serde_json::from_reader(BufReader::new(File::open(&self.instance).unwrap())).unwrap();
+    pub async fn execute(&self, report: &mut Report) {
+        let project = self.project.as_deref().map(Project::open);
+        let instance_path = self.instance.clone().or_else(|| project.as_ref().map(Project::instance)).unwrap();
+        let output = self.output.clone().or_else(|| project.as_ref().map(Project::solution));
+        output.iter().chain(self.dump_dd.iter()).for_each(|path| files::check_output(path, self.force));
+        let mut instance = files::read_instance(&instance_path);
+        if let Some(profile) = self.profile.as_ref() {
+            instance.use_profile(profile)
+                .unwrap_or_else(|message| clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!("{message}\n")).exit());
+            report.json("profile", profile);
+        }
+        if self.objective == Objective::Energy {
+            instance = self.energy_instance(instance).await;
+        }
+        self.check_precision(&instance);
+        match self.decompose {
+            Some(Decomposition::Cluster) => Self::check_decomposition(&instance),
+            None => Self::check_size(&instance),
+        }
         
-        let problem = TspModel{instance};
-        let relaxation = TspRelax;
+        let monitor = Monitor::start();
+        let outcome = match self.decompose {
+            Some(Decomposition::Cluster) => self.solve_by_clusters(&instance, &monitor),
+            None => self.solve_instance(instance, Duration::from_secs(self.timeout), &monitor)
+                .unwrap_or_else(|| {
+                    eprintln!("error: no tour was found within {}s (use a larger --timeout)", self.timeout);
+                    std::process::exit(1)
+                }),
+        };
+        let stats = monitor.stats();
+
+        let best_value = outcome.value / 1000.0; // en kilometres (or kWh)
+        report.json("instance", &instance_path);
+        report.json("objective", format!("{:?}", self.objective).to_lowercase());
+        report.translated("is_exact", outcome.is_exact, "is-exact");
+        report.translated("best_value", best_value, "best-value");
+        if self.objective == Objective::Distance {
+            self.footprint.report(report, best_value);
+        }
+        let messages = report.messages();
+        let mut lines = vec![messages.format("wall-time", &[("value", &format!("{:.3}", stats.wall_time))])];
+        if let Some(cpu) = stats.cpu_time {
+            lines.push(messages.format("cpu-time", &[("value", &format!("{cpu:.3}"))]));
+        }
+        if let Some(rss) = stats.peak_rss_kb {
+            lines.push(messages.format("peak-rss", &[("value", &rss)]));
+        }
+        lines.push(messages.format("explored-nodes", &[("value", &stats.explored_nodes)]));
+        lines.push(messages.format("restricted-dds", &[("value", &stats.restricted_dds)]));
+        lines.push(messages.format("relaxed-dds", &[("value", &stats.relaxed_dds)]));
+        for (k, phase) in outcome.phases.iter().enumerate() {
+            let value = phase.best_value.map_or(messages.text("none"), |v| v.to_string());
+            lines.push(messages.format("phase", &[("phase", &(k + 1)), ("width", &phase.width), ("value", &value),
+                ("exact", &messages.boolean(phase.is_exact)), ("time", &format!("{:.3}", phase.wall_time)),
+                ("explored", &phase.explored_nodes)]));
+        }
+        lines.into_iter().for_each(|line| report.line(line));
+        report.json("stats", &stats);
+        if self.widening {
+            report.json("phases", &outcome.phases);
+        }
 
-        let width = FixedWidth(self.width);
-        let cutoff = TimeBudget::new(Duration::from_secs(self.timeout));
-        let ranking = TspRanking;
-        let mut fringe = SimpleFrontier::new(MaxUB::new(&ranking));
+        let solution = Solution{tour: outcome.tour};
+        let line = report.messages().format("solution", &[("value", &solution.to_text())]);
+        report.field("solution", &solution.tour, line);
+
+        if let Some(output) = output.as_ref() {
+            files::write_text(output, &serde_json::to_string_pretty(&report.document()).unwrap(), self.force);
+            report.json("output", output);
+        }
+        if let Some(project) = project {
+            project.log_command("solve", report.document());
+        }
+    }
+
+    /// Solves the given instance as a whole with these settings. This returns None
+    /// when no tour was found within the time budget.
+    pub fn solve(&self, instance: Instance) -> Option<Outcome> {
+        self.solve_instance(instance, Duration::from_secs(self.timeout), &Monitor::start())
+    }
 
-        let mut solver = ParallelSolver::new(&problem, &relaxation, &ranking, &width, &cutoff, &mut fringe);
+    /// Returns the instance whose travel costs are the energy (in Wh) needed to
+    /// travel between the destinations of the given instance
+    async fn energy_instance(&self, mut instance: Instance) -> Instance {
+        let url = self.elevation_url.as_ref().expect("the energy objective needs an --elevation-url");
+        let elevations = elevation::elevations(url, &instance.destinations).await
+            .unwrap_or_else(|message| {
+                eprintln!("error: {message}");
+                std::process::exit(1)
+            });
+        instance.distances = self.vehicle.matrix(&instance.costs(), &elevations);
+        instance.overrides.clear(); // they have been applied already
+        instance
+    }
 
-        let Completion{best_value, is_exact} = solver.maximize();
+    /// Exits with an error when some travel cost of the instance cannot be represented
+    /// on 32 bits with the requested precision
+    fn check_precision(&self, instance: &Instance) {
+        let Some(precision) = self.precision else {
+            return;
+        };
+        let usable = matrix::max_precision(&instance.costs());
+        if usable.is_some_and(|p| precision <= p) {
+            return;
+        }
+        let hint = match usable {
+            Some(p) => format!("use --precision {p} or less"),
+            None => "omit --precision".to_string(),
+        };
+        clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!(
+            "the largest travel cost of the instance cannot be represented on 32 bits with {precision} decimals: {hint}\n"))
+            .exit();
+    }
 
-        let best_value = best_value.map(|v| v as f32 / -100_000_000.0).unwrap_or(0.0); // en kilometres
-        println!("is exact {is_exact}");
-        println!("best value {best_value}");
+    /// Exits with an error when the instance has too many destinations to be solved
+    /// as a whole
+    fn check_size(instance: &Instance) {
+        if instance.len() > MAX_DESTINATIONS {
+            clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!(
+                "the instance has {} destinations whereas the solver handles at most {MAX_DESTINATIONS} at once: \
+                use --decompose cluster\n", instance.len()))
+                .exit();
+        }
+    }
 
-        let mut sol = String::new();
-        solver.best_solution().unwrap()
-            .iter().map(|d| d.value)
-            .for_each(|v| sol.push_str(&format!("{v} ")));
+    /// Exits with an error when the instance cannot be decomposed by cluster: it must
+    /// be a plain TSP instance carrying cluster labels
+    fn check_decomposition(instance: &Instance) {
+        let problem = if !instance.variant.is_tsp() {
+            "a gtsp instance already visits one destination per cluster"
+        } else if instance.cluster_members().is_none() {
+            "the instance has no cluster labels"
+        } else {
+            return;
+        };
+        clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!(
+            "the instance cannot be decomposed by cluster: {problem} (omit --decompose)\n"))
+            .exit();
+    }
 
-        println!("solution: {sol}");
+    /// Solves the complete instance with branch and bound within the given time budget.
+    /// This returns None when no tour was found within that budget.
+    fn solve_instance(&self, instance: Instance, timeout: Duration, monitor: &Monitor) -> Option<Outcome> {
+        if let Some(precision) = self.precision {
+            self.optimize(TspModel::fixed_point(instance, precision), timeout, monitor)
+        } else {
+            self.optimize(TspModel::new(instance), timeout, monitor)
+        }
     }
-}
\ No newline at end of file
+
+    /// Solves the given model and returns the best tour that was found (if any)
+    fn optimize<C: Cost>(&self, mut problem: TspModel<C>, timeout: Duration, monitor: &Monitor) -> Option<Outcome> {
+        problem.branching = self.branching;
+        problem.visit_optional = self.visit_optional;
+        let tracer = Tracer{enabled: self.trace, scale: problem.costs.scale()};
+        let relaxation = TracingRelax{inner: TspRelax{model: &problem}, tracer: &tracer};
+        let execution = if self.trace { Execution::Sequential } else { self.solver };
+
+        let cutoff = TimeBudget::new(timeout);
+        let ranking = TspRanking{model: &problem, criterion: self.ranking};
+        if let Some(path) = self.dump_dd.as_ref() {
+            let dot = dump::relaxed_dd(&problem, &TspRelax{model: &problem}, &ranking, self.width);
+            files::write_text(path, &dot, self.force);
+        }
+
+        let mut phases = vec![];
+        let (best_value, is_exact, decisions) = match self.algorithm {
+            Algorithm::BranchAndBound if self.widening => {
+                let (best_value, is_exact, decisions, widening) = Self::widening(&problem, &relaxation, &ranking, self.width, execution, timeout, monitor, &tracer);
+                phases = widening;
+                (best_value, is_exact, decisions)
+            },
+            Algorithm::BranchAndBound => Self::branch_and_bound(&problem, &relaxation, &ranking, self.width, execution, &cutoff, None, monitor, &tracer),
+            Algorithm::RestrictedDd => Self::restricted_dd(&problem, &relaxation, &ranking, self.width, &cutoff, monitor),
+        };
+
+        let value = best_value.map(|v| problem.travel_cost(v)).unwrap_or(0.0);
+        let mut decisions = decisions?;
+        decisions.sort_unstable_by_key(|d| d.variable.id());
+        let mut tour = std::iter::once(0)
+            .chain(decisions.iter().map(|d| d.value as usize).filter(|v| *v != 0))
+            .collect::<Vec<_>>();
+        local_search::normalize(&problem.instance.costs(), &mut tour);
+
+        Some(Outcome { is_exact, value, tour, phases })
+    }
+
+    /// Runs the branch and bound with the given width. The nodes which cannot improve
+    /// on the incumbent (if any) are pruned.
+    #[allow(clippy::too_many_arguments)]
+    fn branch_and_bound<C: Cost>(
+        problem: &TspModel<C>,
+        relaxation: &(dyn Relaxation<State = TspState> + Send + Sync),
+        ranking: &TspRanking<C>,
+        width: usize,
+        execution: Execution,
+        cutoff: &(dyn Cutoff + Send + Sync),
+        incumbent: Option<isize>,
+        monitor: &Monitor,
+        tracer: &Tracer,
+    ) -> (Option<isize>, bool, Option<Vec<Decision>>) {
+        let width = FixedWidth(width);
+        let inner = IncumbentFrontier{inner: SimpleFrontier::new(MaxUB::new(ranking)), incumbent};
+        let mut fringe = TracingFrontier{inner, tracer};
+        monitor.run(|| {
+            tracer.install();
+            let mut solver = ParallelSolver::<_, TracingMdd<CountingMdd<DefaultMDD<TspState>>>>::custom(
+                problem, relaxation, ranking, &width, cutoff, &mut fringe, execution.threads());
+            let Completion{best_value, is_exact} = solver.maximize();
+            (best_value, is_exact, solver.best_solution())
+        })
+    }
+
+    /// Runs the branch and bound with geometrically increasing widths until the
+    /// optimality of the best tour is proven or the time budget is exhausted
+    #[allow(clippy::too_many_arguments)]
+    fn widening<C: Cost>(
+        problem: &TspModel<C>,
+        relaxation: &(dyn Relaxation<State = TspState> + Send + Sync),
+        ranking: &TspRanking<C>,
+        width: usize,
+        execution: Execution,
+        timeout: Duration,
+        monitor: &Monitor,
+        tracer: &Tracer,
+    ) -> (Option<isize>, bool, Option<Vec<Decision>>, Vec<Phase>) {
+        let start = Instant::now();
+        let mut best: (Option<isize>, Option<Vec<Decision>>) = (None, None);
+        let mut phases = vec![];
+        let mut width = width.max(1);
+        loop {
+            let phase_start = Instant::now();
+            let explored = monitor.stats().explored_nodes;
+            let cutoff = TimeBudget::new(timeout.saturating_sub(start.elapsed()));
+            let (value, is_exact, decisions) = Self::branch_and_bound(problem, relaxation, ranking, width, execution, &cutoff, best.0, monitor, tracer);
+            if value > best.0 {
+                best = (value, decisions);
+            }
+            phases.push(Phase {
+                width,
+                best_value: best.0.map(|v| problem.travel_cost(v) / 1000.0), // en kilometres
+                is_exact,
+                wall_time: phase_start.elapsed().as_secs_f64(),
+                explored_nodes: monitor.stats().explored_nodes - explored,
+            });
+            if is_exact || start.elapsed() >= timeout {
+                return (best.0, is_exact, best.1, phases);
+            }
+            width *= widening::GROWTH;
+        }
+    }
+
+    /// Compiles one single restricted decision diagram of the given width (from the
+    /// root) and returns the value, exactness and decisions of its best tour
+    fn restricted_dd<C: Cost>(
+        problem: &TspModel<C>,
+        relaxation: &dyn Relaxation<State = TspState>,
+        ranking: &TspRanking<C>,
+        width: usize,
+        cutoff: &dyn Cutoff,
+        monitor: &Monitor,
+    ) -> (Option<isize>, bool, Option<Vec<Decision>>) {
+        let root = SubProblem {
+            state: Arc::new(problem.initial_state()),
+            value: problem.initial_value(),
+            path: vec![],
+            ub: isize::MAX,
+        };
+        let input = CompilationInput {
+            comp_type: CompilationType::Restricted,
+            problem,
+            relaxation,
+            ranking,
+            cutoff,
+            max_width: width,
+            residual: root,
+            best_lb: isize::MIN,
+        };
+        monitor.run(|| {
+            let mut dd = CountingMdd::<DefaultMDD<TspState>>::default();
+            let completed = dd.compile(&input).is_ok();
+            (dd.best_value(), completed && dd.is_exact(), dd.best_solution())
+        })
+    }
+
+    /// Solves the instance by first sequencing its clusters, then ordering the
+    /// destinations within each cluster. The partial tours are stitched together
+    /// and the result is polished with a local search. The parts which are too large
+    /// for the solver (or for which it finds no tour in time) are ordered by the
+    /// nearest neighbour heuristic instead. The instance must be decomposable (see
+    /// `check_decomposition`).
+    fn solve_by_clusters(&self, instance: &Instance, monitor: &Monitor) -> Outcome {
+        assert!(instance.variant.is_tsp(), "a gtsp instance cannot be decomposed by cluster");
+        let members = instance.cluster_members()
+            .expect("the instance has no cluster labels, it cannot be decomposed by cluster");
+        let budget = Duration::from_secs(self.timeout) / (members.len() as u32 + 2);
+        let costs = instance.costs();
+
+        let order = self.solve_part(Self::clusters_instance(instance, &costs, &members), budget, monitor);
+
+        let mut tour: Vec<usize> = vec![];
+        for cluster in order {
+            let ids = &members[cluster];
+            let cycle = self.solve_part(instance.subset(ids), budget, monitor)
+                .into_iter()
+                .map(|i| ids[i])
+                .collect::<Vec<_>>();
+            Self::stitch(&costs, &mut tour, &cycle);
+        }
+
+        let polish = LocalSearch { time_limit: budget, ..LocalSearch::default() };
+        let value = polish.improve(&costs, &mut tour);
+        local_search::normalize(&costs, &mut tour);
+        Outcome { is_exact: false, value, tour, phases: vec![] }
+    }
+
+    /// Returns a tour of one part of a decomposed instance: it is found by the solver
+    /// if the part is small enough, and by the nearest neighbour heuristic otherwise
+    fn solve_part(&self, part: Instance, budget: Duration, monitor: &Monitor) -> Vec<usize> {
+        let costs = part.costs();
+        let solved = if part.len() <= MAX_DESTINATIONS {
+            self.solve_instance(part, budget, monitor)
+        } else {
+            None
+        };
+        solved.map_or_else(|| nearest_neighbour(&costs), |outcome| outcome.tour)
+    }
+
+    /// Creates the instance whose destinations are the clusters of the given instance.
+    /// The cost between two clusters is that of the cheapest edge between them.
+    fn clusters_instance(instance: &Instance, costs: &[Vec<f32>], members: &[Vec<usize>]) -> Instance {
+        let destinations = members.iter()
+            .map(|ids| {
+                let n = ids.len() as f32;
+                let longitude = ids.iter().map(|i| instance.destinations[*i].longitude).sum::<f32>() / n;
+                let latitude = ids.iter().map(|i| instance.destinations[*i].latitude).sum::<f32>() / n;
+                Location { longitude, latitude }
+            })
+            .collect();
+        let distances = members.iter().enumerate()
+            .map(|(a, from)| members.iter().enumerate()
+                .map(|(b, to)| if a == b {
+                    0.0
+                } else {
+                    from.iter()
+                        .flat_map(|i| to.iter().map(|j| costs[*i][*j]))
+                        .fold(f32::INFINITY, f32::min)
+                })
+                .collect())
+            .collect();
+        Instance { destinations, distances, ..Instance::default() }
+    }
+
+    /// Appends the cycle to the tour, entering it at the destination that is the
+    /// closest to the end of the tour
+    fn stitch(distances: &[Vec<f32>], tour: &mut Vec<usize>, cycle: &[usize]) {
+        if let Some(last) = tour.last().copied() {
+            let entry = (0..cycle.len())
+                .min_by(|a, b| distances[last][cycle[*a]].total_cmp(&distances[last][cycle[*b]]))
+                .unwrap_or(0);
+            tour.extend(cycle[entry..].iter().chain(cycle[..entry].iter()));
+        } else {
+            tour.extend_from_slice(cycle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use osrm_client::Location;
+    use proptest::{prelude::*, collection::vec};
+
+    use crate::{instance::Instance, check::violations, selftest::random};
+    use super::{Solve, local_search::tour_cost};
+
+    /// Generates the instances of at most 8 destinations whose travel costs are
+    /// (possibly asymmetric) whole numbers of metres, so that the cost of any tour
+    /// is computed exactly
+    fn instances() -> impl Strategy<Value = Instance> {
+        (2..=8_usize)
+            .prop_flat_map(|n| vec(vec(1..20_000_u32, n), n))
+            .prop_map(|costs| {
+                let distances = costs.iter().enumerate()
+                    .map(|(i, row)| row.iter().enumerate().map(|(j, c)| if i == j { 0.0 } else { *c as f32 }).collect())
+                    .collect();
+                let destinations = vec![Location { longitude: 4.35, latitude: 50.85 }; costs.len()];
+                Instance { destinations, distances, ..Instance::default() }
+            })
+    }
+
+    #[test]
+    fn the_tour_keeps_the_direction_of_a_required_edge() {
+        // a symmetric instance where both directions of any tour cost the same
+        let n = 6;
+        let distances = (0..n)
+            .map(|i| (0..n).map(|j| (i as f32 - j as f32).abs() * 1000.0).collect())
+            .collect();
+        let destinations = vec![Location { longitude: 4.35, latitude: 50.85 }; n];
+        let mut instance = Instance { destinations, distances, ..Instance::default() };
+        instance.required.push((0, 5));
+        let outcome = Solve::default().solve(instance.clone()).unwrap();
+        assert_eq!(violations(&instance, &outcome.tour), Vec::<String>::new());
+        assert_eq!(outcome.tour[1], 5);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn the_value_is_the_cost_of_the_tour(instance in instances()) {
+            let outcome = Solve::default().solve(instance.clone()).unwrap();
+            prop_assert_eq!(violations(&instance, &outcome.tour), Vec::<String>::new());
+            prop_assert_eq!(outcome.value, tour_cost(&instance.costs(), &outcome.tour));
+        }
+
+        #[test]
+        fn merges_never_cut_the_optimal_tour(instance in instances()) {
+            let outcome = Solve { width: 2, ..Solve::default() }.solve(instance.clone()).unwrap();
+            prop_assert!(outcome.is_exact);
+            prop_assert_eq!(outcome.value, random::optimum(&instance.costs()));
+        }
+    }
+}
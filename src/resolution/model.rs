@@ -1,8 +1,15 @@
+use clap::ValueEnum;
 use ddo::{Problem, Variable, Decision, Relaxation, StateRanking};
 use smallbitset::Set64;
 
 use crate::instance::Instance;
 
+use super::matrix::{CostMatrix, Cost};
+
+/// The largest number of destinations the model can deal with: the destinations
+/// which are visited or left to visit are stored in 64 bits sets
+pub const MAX_DESTINATIONS: usize = 64;
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct TspState {
     depth:       usize,
@@ -11,12 +18,137 @@ pub struct TspState {
     might_visit: Set64,
 }
 
+impl std::fmt::Display for TspState {
+    /// The current position(s) followed by the destinations that must and might
+    /// still be visited
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ids = |set: Set64| set.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(",");
+        write!(f, "at {{{}}} must {{{}}} might {{{}}}", ids(self.current), ids(self.must_visit), ids(self.might_visit))
+    }
+}
+
+/// The order in which the values of a domain are explored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum BranchingOrder {
+    /// Destinations are explored by increasing identifier
+    Natural,
+    /// Destinations are explored by increasing transition cost, so that the most
+    /// promising successors are developed first
+    #[default]
+    Nearest,
+}
+
+/// The criteria used to rank the states (the best ones being kept when a decision
+/// diagram is restricted or relaxed)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Ranking {
+    /// The states which have the fewest destinations left to visit come first
+    #[default]
+    Depth,
+    /// The states which have completed the most clusters come first (this requires
+    /// cluster labels, the ties are broken by depth)
+    Clusters,
+    /// The states having the best bound on the remaining travel cost come first
+    /// (the ties are broken by depth)
+    Bound,
+}
+
+/// How the destinations which are tagged optional are dealt with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum VisitOptional {
+    /// The optional destinations are never visited
+    Never,
+    /// The optional destinations may be visited, which happens when it does not
+    /// make the tour any longer
+    #[default]
+    IfFree,
+    /// The optional destinations are visited just like the mandatory ones
+    Always,
+}
+
 #[derive(Debug, Clone)]
-pub struct TspModel {
+pub struct TspModel<C: Cost = f32> {
     pub instance: Instance,
+    /// The travel costs laid out for fast row scans
+    pub costs: CostMatrix<C>,
+    /// The cost of the cheapest edge leaving each destination
+    pub cheapest_out: Vec<C>,
+    /// For each destination, all other destinations sorted by increasing cost
+    pub neighbours: Vec<Vec<u8>>,
+    /// For each destination, the destinations which can be reached from there
+    /// (that is, along an edge which is neither forbidden nor conflicting with a
+    /// required edge)
+    pub reachable: Vec<Set64>,
+    /// For each destination, the destinations that no longer need to be visited
+    /// once it has been visited (itself, or its whole cluster for a GTSP)
+    pub covers: Vec<Set64>,
+    /// For each destination, the cost of the cheapest edge leaving any of the
+    /// destinations it covers
+    pub cheapest_cover_out: Vec<C>,
+    /// The destinations of each cluster (empty without cluster labels)
+    pub clusters: Vec<Set64>,
+    /// The order in which the domain values are explored
+    pub branching: BranchingOrder,
+    /// Whether the optional destinations are visited
+    pub visit_optional: VisitOptional,
+}
+
+impl TspModel<f32> {
+    /// Creates a model working directly with the floating point costs of the instance
+    pub fn new(instance: Instance) -> Self {
+        let costs = CostMatrix::new(&instance.costs());
+        Self::with_costs(instance, costs)
+    }
 }
 
-impl Problem for TspModel {
+impl TspModel<i32> {
+    /// Creates a model working with fixed point costs rounded to `precision` decimals
+    pub fn fixed_point(instance: Instance, precision: u32) -> Self {
+        let costs = CostMatrix::new(&instance.costs()).quantize(precision);
+        Self::with_costs(instance, costs)
+    }
+}
+
+impl <C: Cost> TspModel<C> {
+    fn with_costs(instance: Instance, costs: CostMatrix<C>) -> Self {
+        let n = costs.len();
+        let cheapest_out: Vec<C> = (0..n).map(|i| costs.min_outgoing(i)).collect();
+        let neighbours = (0..n)
+            .map(|i| {
+                let mut adj = (0..n as u8).filter(|j| *j as usize != i).collect::<Vec<_>>();
+                adj.sort_by_key(|j| costs.get(i, *j as usize).fixed());
+                adj
+            })
+            .collect();
+        let reachable = (0..n)
+            .map(|i| (0..n)
+                .filter(|j| *j != i && instance.is_allowed(i, *j))
+                .fold(Set64::empty(), |mut set, j| set.insert(j as u8)))
+            .collect();
+        let covers: Vec<Set64> = (0..n)
+            .map(|i| instance.covered_by(i).into_iter().fold(Set64::empty(), |mut set, j| set.insert(j as u8)))
+            .collect();
+        let cheapest_cover_out = covers.iter()
+            .map(|cover| cover.iter()
+                .map(|j| cheapest_out[j as usize])
+                .fold(C::INFINITY, C::min))
+            .collect();
+        let clusters = instance.cluster_members().unwrap_or_default().iter()
+            .map(|ids| ids.iter().fold(Set64::empty(), |mut set, j| set.insert(*j as u8)))
+            .collect();
+        Self { instance, costs, cheapest_out, neighbours, reachable, covers, cheapest_cover_out, clusters,
+            branching: BranchingOrder::default(), visit_optional: VisitOptional::default() }
+    }
+
+    /// Converts a value of the solver back into a travel cost expressed in the
+    /// units of the instance (the division is made in double precision, as the
+    /// scaled values of long tours exceed the mantissa of a f32)
+    pub fn travel_cost(&self, value: isize) -> f32 {
+        (-(value as f64) / self.costs.scale() as f64) as f32
+    }
+}
+
+impl <C: Cost> Problem for TspModel<C> {
     type State = TspState;
 
     fn nb_variables(&self) -> usize {
@@ -25,15 +157,23 @@ impl Problem for TspModel {
 
     fn initial_state(&self) -> Self::State {
         let mut must = Set64::empty();
+        let mut might = Set64::empty();
         for i in 0..self.nb_variables() {
-            must = must.insert(i as u8);
+            match (self.instance.is_optional(i), self.visit_optional) {
+                (false, _) | (true, VisitOptional::Always) => must = must.insert(i as u8),
+                (true, VisitOptional::IfFree) => might = might.insert(i as u8),
+                (true, VisitOptional::Never) => {},
+            }
         }
+        // leaving the depot covers its cluster, but the tour must still get back to it
+        must = must.diff(self.covers[0]).insert(0);
+        might = might.diff(self.covers[0]);
 
         TspState {
             depth: 0,
             current: Set64::singleton(0),
             must_visit: must,
-            might_visit: Set64::empty(),
+            might_visit: might,
         }
     }
 
@@ -41,20 +181,36 @@ impl Problem for TspModel {
         0
     }
 
+    /// Visiting a destination covers all the destinations of `covers`. Going back to
+    /// the depot ends the tour: the optional destinations that have not been
+    /// visited so far are skipped.
     fn transition(&self, state: &Self::State, decision: ddo::Decision) -> Self::State {
+        let to = decision.value as u8;
+        if to == 0 {
+            return TspState{
+                depth       : state.depth + 1,
+                current     : Set64::singleton(0),
+                must_visit  : state.must_visit.remove(0),
+                might_visit : Set64::empty(),
+            };
+        }
+        let covered = self.covers[to as usize];
         TspState{
             depth       : state.depth + 1,
-            current     : Set64::singleton(decision.value as u8),
-            must_visit  : state.must_visit.remove(decision.value as u8),
-            might_visit : state.might_visit.remove(decision.value as u8),
+            current     : Set64::singleton(to),
+            must_visit  : state.must_visit.diff(covered),
+            might_visit : state.might_visit.diff(covered),
         }
     }
 
     fn transition_cost(&self, state: &Self::State, decision: ddo::Decision) -> isize {
+        if state.must_visit.is_empty() {
+            return 0; // the tour is over already
+        }
         let to = decision.value as usize;
         state.current.iter()
-            .map(|from| self.instance.distances[from as usize][to])
-            .map(|cost| (cost * 100_000.0).round() as isize)
+            .map(|from| self.costs.get(from as usize, to))
+            .map(Cost::fixed)
             .min()
             .map(|v| -v) // it is a minimization problem
             .unwrap_or(0)
@@ -68,23 +224,103 @@ impl Problem for TspModel {
             .map(Variable)
     }
 
+    /// Once the tour is over (the depot has been reached back), the remaining variables
+    /// are all assigned the dummy value 0. Otherwise, the domain comprises all the
+    /// destinations that can still be visited, and the depot as soon as all the
+    /// mandatory destinations have been visited.
     fn for_each_in_domain(&self, var: ddo::Variable, state: &Self::State, f: &mut dyn ddo::DecisionCallback) {
-        let dest = state.must_visit.union(state.might_visit);
-        if dest.len() == 1 {
+        if state.must_visit.is_empty() {
+            f.apply(Decision{variable: var, value: 0});
+            return;
+        }
+        let allowed = state.current.iter()
+            .fold(Set64::empty(), |set, from| set.union(self.reachable[from as usize]));
+        let back_home = allowed.contains(0) || state.current.contains(0);
+        if state.must_visit.remove(0).is_empty() && back_home {
             f.apply(Decision{variable: var, value: 0});
+        }
+        let dest = state.must_visit.union(state.might_visit).remove(0).inter(allowed);
+        if !dest.is_empty() {
+            match self.branching {
+                BranchingOrder::Natural => {
+                    for to in dest.iter() {
+                        if to == 0 {continue;}
+                        
+                        f.apply(Decision{variable: var, value: to as isize});
+                    }
+                },
+                BranchingOrder::Nearest => self.nearest_first(state, dest, &mut |to| {
+                    f.apply(Decision{variable: var, value: to as isize});
+                }),
+            }
+        }
+    }
+}
+
+impl <C: Cost> TspModel<C> {
+    /// Every destination that must still be visited will have to be left at some point
+    /// (the depot being left from the current position). Hence the sum of the cheapest
+    /// outgoing edges gives a lower bound on the remaining travel cost. For a GTSP,
+    /// only one destination per cluster is left: the cheapest of them is counted.
+    pub fn remaining_bound(&self, state: &TspState) -> isize {
+        if state.must_visit.is_empty() {
+            return 0;
+        }
+        let leave_current = state.current.iter()
+            .map(|c| self.cheapest_out[c as usize].fixed())
+            .min()
+            .unwrap_or(0);
+        // saturating, because a destination whose edges are all forbidden cannot be left
+        let mut leave_others = 0_isize;
+        let mut rest = state.must_visit.remove(0);
+        while let Some(c) = rest.iter().next() {
+            let c = c as usize;
+            leave_others = leave_others.saturating_add(self.cheapest_cover_out[c].fixed());
+            rest = rest.diff(self.covers[c]);
+        }
+
+        -(leave_current.saturating_add(leave_others))
+    }
+
+    /// Returns the number of clusters all of whose destinations have been visited
+    /// (or skipped) in the given state
+    fn completed_clusters(&self, state: &TspState) -> usize {
+        let left = state.must_visit.union(state.might_visit);
+        self.clusters.iter().filter(|c| c.inter(left).is_empty()).count()
+    }
+
+    /// Calls `f` on each (non depot) destination of `dest` by increasing cost of the
+    /// transition that would lead there from the given state. This is the hot path
+    /// of the solver: nothing is allocated on the heap.
+    fn nearest_first(&self, state: &TspState, dest: Set64, f: &mut dyn FnMut(u8)) {
+        if state.current.len() == 1 {
+            let from = state.current.iter().next().unwrap() as usize;
+            self.neighbours[from].iter().copied()
+                .filter(|to| *to != 0 && dest.contains(*to))
+                .for_each(f);
         } else {
-            for to in dest.iter() {
-                if to == 0 {continue;}
-                
-                f.apply(Decision{variable: var, value: to as isize});
+            // a merged state: the destinations are sorted in a buffer on the stack
+            let mut buffer = [(0_isize, 0_u8); 64];
+            let mut len = 0;
+            for to in dest.iter().filter(|to| *to != 0) {
+                let cost = state.current.iter()
+                    .map(|from| self.costs.get(from as usize, to as usize).fixed())
+                    .min()
+                    .unwrap_or(0);
+                buffer[len] = (cost, to);
+                len += 1;
             }
+            buffer[..len].sort_unstable();
+            buffer[..len].iter().for_each(|(_, to)| f(*to));
         }
     }
 }
 
-pub struct TspRelax;
+pub struct TspRelax<'a, C: Cost = f32> {
+    pub model: &'a TspModel<C>,
+}
 
-impl Relaxation for TspRelax {
+impl <C: Cost> Relaxation for TspRelax<'_, C> {
     type State = TspState;
 
     fn merge(&self, states: &mut dyn Iterator<Item = &Self::State>) -> Self::State {
@@ -119,17 +355,38 @@ impl Relaxation for TspRelax {
     ) -> isize {
         cost
     }
+
+    fn fast_upper_bound(&self, state: &Self::State) -> isize {
+        self.model.remaining_bound(state)
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
-pub struct TspRanking;
-impl StateRanking for TspRanking {
-    type State = TspState;
+pub struct TspRanking<'a, C: Cost = f32> {
+    pub model: &'a TspModel<C>,
+    pub criterion: Ranking,
+}
 
-    fn compare(&self, a: &Self::State, b: &Self::State) -> std::cmp::Ordering {
+impl <C: Cost> TspRanking<'_, C> {
+    /// Compares the states by depth: the fewer destinations left, the better
+    fn by_depth(a: &TspState, b: &TspState) -> std::cmp::Ordering {
         a.must_visit.len().cmp(&b.must_visit.len())
             .then_with(|| a.might_visit.len().cmp(&b.might_visit.len()))
             .then_with(|| a.current.len().cmp(&b.current.len()))
             .reverse()
     }
-}
\ No newline at end of file
+}
+
+impl <C: Cost> StateRanking for TspRanking<'_, C> {
+    type State = TspState;
+
+    fn compare(&self, a: &Self::State, b: &Self::State) -> std::cmp::Ordering {
+        match self.criterion {
+            Ranking::Depth => Self::by_depth(a, b),
+            Ranking::Clusters => self.model.completed_clusters(a).cmp(&self.model.completed_clusters(b))
+                .then_with(|| Self::by_depth(a, b)),
+            Ranking::Bound => self.model.remaining_bound(a).cmp(&self.model.remaining_bound(b))
+                .then_with(|| Self::by_depth(a, b)),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! This module provides the command to improve an existing tour with local search.
+
+use std::time::Duration;
+
+use clap::Args;
+
+use crate::{report::Report, files, check};
+
+use super::local_search::{LocalSearch, Move, tour_cost};
+
+/// This command improves an existing tour (e.g. produced by some other tool) with
+/// a time bounded local search, and outputs the improved tour.
+#[derive(Debug, Args)]
+pub struct Polish {
+    /// The path to the instance file (`-` for the standard input)
+    #[clap(short, long)]
+    pub instance: String,
+    /// The tour to improve (sequence of destination identifiers 0..n), or the path
+    /// of a file comprising it (`-` for the standard input)
+    #[clap(short, long)]
+    pub solution: String,
+    /// The moves the local search is allowed to perform
+    #[clap(short, long, value_enum, value_delimiter=',', default_values_t=[Move::TwoOpt, Move::OrOpt])]
+    pub moves: Vec<Move>,
+    /// The maximum time (in seconds) spent improving the tour
+    #[clap(short, long, default_value="10")]
+    pub timeout: u64,
+    /// If present, the path where to write the improved tour (`-` for the standard output)
+    #[clap(short, long)]
+    pub output: Option<String>,
+    /// Overwrite the output file if it already exists
+    #[clap(long)]
+    pub force: bool,
+}
+
+impl Polish {
+    /// Executes this command
+    pub async fn execute(&self, report: &mut Report) {
+        if let Some(output) = self.output.as_ref() {
+            files::check_output(output, self.force);
+        }
+        let instance = files::read_instance(&self.instance);
+        let mut solution = files::read_solution(&self.solution);
+        check::ensure_valid(&instance, &solution.tour, &self.solution);
+
+        let costs = instance.costs();
+        let initial = tour_cost(&costs, &solution.tour);
+        let search = LocalSearch { moves: self.moves.clone(), time_limit: Duration::from_secs(self.timeout) };
+        let improved = search.improve(&costs, &mut solution.tour);
+
+        let initial = initial / 1000.0;   // en kilometres
+        let improved = improved / 1000.0; // en kilometres
+        report.json("instance", &self.instance);
+        report.translated("initial_value", initial, "initial-value");
+        report.translated("best_value", improved, "best-value");
+
+        let sol = solution.to_text();
+        if let Some(output) = self.output.as_ref() {
+            files::write_text(output, &sol, self.force);
+            report.json("output", output);
+        } else {
+            let line = report.messages().format("solution", &[("value", &sol)]);
+            report.field("solution", &solution.tour, line);
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! This module gathers statistics about the resources consumed when solving an
+//! instance: the wall and cpu times, the peak memory usage and the amount of work
+//! performed by the solver (explored nodes and compiled decision diagrams).
+//!
+//! The work counters are collected by a thin wrapper around the decision diagrams
+//! which are handed over to the solver. The solver creates its decision diagrams by
+//! itself (with `Default`): hence they pick the counters of the monitor which is
+//! running (see `Monitor::run`). The runs are serialized so that the decision
+//! diagrams of concurrent runs (e.g. in the tests) never update the wrong counters.
+
+use std::{sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex, PoisonError}, time::{Duration, Instant}};
+
+use ddo::{CompilationInput, CompilationType, Completion, DecisionDiagram, Reason, Solution, SubProblem};
+use serde::Serialize;
+
+/// Held during each run of the solver, so that only one of them runs at once
+static RUNNING: Mutex<()> = Mutex::new(());
+/// The counters of the running monitor (if any)
+static ACTIVE: Mutex<Option<Arc<Counters>>> = Mutex::new(None);
+
+/// The counters which are incremented by the solver threads
+#[derive(Debug, Default)]
+pub struct Counters {
+    restricted: AtomicUsize,
+    relaxed: AtomicUsize,
+}
+
+/// The resources consumed by one run of the solver
+#[derive(Debug, Clone, Serialize)]
+pub struct RunStats {
+    /// The elapsed wall clock time (in seconds)
+    pub wall_time: f64,
+    /// The cpu time consumed by all threads of the process (in seconds), if known
+    pub cpu_time: Option<f64>,
+    /// The peak resident set size of the process (in kilobytes), if known
+    pub peak_rss_kb: Option<u64>,
+    /// The number of nodes explored by the solver, that is the nodes for which it
+    /// compiled a restricted decision diagram (the nodes it pruned straight away
+    /// against the best value are not counted)
+    pub explored_nodes: usize,
+    /// The number of restricted decision diagrams that were compiled
+    pub restricted_dds: usize,
+    /// The number of relaxed decision diagrams that were compiled
+    pub relaxed_dds: usize,
+}
+
+/// Measures the resources consumed from its creation on
+#[derive(Debug)]
+pub struct Monitor {
+    start: Instant,
+    cpu_start: Option<Duration>,
+    counters: Arc<Counters>,
+}
+
+impl Monitor {
+    pub fn start() -> Self {
+        Self {
+            start: Instant::now(),
+            cpu_start: cpu_time(),
+            counters: Arc::default(),
+        }
+    }
+
+    /// Runs the solver: the decision diagrams created meanwhile update the counters
+    /// of this monitor
+    pub fn run<T>(&self, solver: impl FnOnce() -> T) -> T {
+        let _running = RUNNING.lock().unwrap_or_else(PoisonError::into_inner);
+        *ACTIVE.lock().unwrap_or_else(PoisonError::into_inner) = Some(self.counters.clone());
+        let result = solver();
+        *ACTIVE.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        result
+    }
+
+    /// Returns the resources consumed so far
+    pub fn stats(&self) -> RunStats {
+        RunStats {
+            wall_time: self.start.elapsed().as_secs_f64(),
+            cpu_time: cpu_time().zip(self.cpu_start).map(|(now, start)| (now - start).as_secs_f64()),
+            peak_rss_kb: peak_rss_kb(),
+            explored_nodes: self.counters.restricted.load(Ordering::Relaxed),
+            restricted_dds: self.counters.restricted.load(Ordering::Relaxed),
+            relaxed_dds: self.counters.relaxed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A decision diagram which counts the restricted and relaxed compilations (each
+/// node explored by the solver is compiled into a restricted decision diagram)
+pub struct CountingMdd<D> {
+    inner: D,
+    counters: Option<Arc<Counters>>,
+}
+
+impl <D: Default> Default for CountingMdd<D> {
+    fn default() -> Self {
+        Self { inner: D::default(), counters: ACTIVE.lock().unwrap_or_else(PoisonError::into_inner).clone() }
+    }
+}
+
+impl <D: DecisionDiagram> DecisionDiagram for CountingMdd<D> {
+    type State = D::State;
+
+    fn compile(&mut self, input: &CompilationInput<Self::State>) -> Result<Completion, Reason> {
+        if let Some(counters) = self.counters.as_ref() {
+            match input.comp_type {
+                CompilationType::Restricted => { counters.restricted.fetch_add(1, Ordering::Relaxed); },
+                CompilationType::Relaxed    => { counters.relaxed.fetch_add(1, Ordering::Relaxed); },
+                CompilationType::Exact      => {},
+            }
+        }
+        self.inner.compile(input)
+    }
+
+    fn is_exact(&self) -> bool {
+        self.inner.is_exact()
+    }
+
+    fn best_value(&self) -> Option<isize> {
+        self.inner.best_value()
+    }
+
+    fn best_solution(&self) -> Option<Solution> {
+        self.inner.best_solution()
+    }
+
+    fn drain_cutset<F>(&mut self, func: F)
+    where F: FnMut(SubProblem<Self::State>)
+    {
+        self.inner.drain_cutset(func)
+    }
+}
+
+/// Returns the cpu time consumed by all threads of the process so far (only
+/// available on unix)
+#[cfg(unix)]
+fn cpu_time() -> Option<Duration> {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+    // SAFETY: getrusage only writes into the given structure
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    // SAFETY: the structure has been initialized by the successful call
+    let usage = unsafe { usage.assume_init() };
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    Some(time(usage.ru_utime) + time(usage.ru_stime))
+}
+
+/// Returns the cpu time consumed by the process so far (only available on unix)
+#[cfg(not(unix))]
+fn cpu_time() -> Option<Duration> {
+    None
+}
+
+/// Returns the peak resident set size of the process (only available on linux)
+fn peak_rss_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status.lines()
+        .find(|l| l.starts_with("VmHWM:"))?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+}
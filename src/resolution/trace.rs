@@ -0,0 +1,213 @@
+//! This module writes a human readable log of what the branch and bound does on
+//! the standard error: the nodes selected from the frontier, the decision diagrams
+//! compiled from them, the states which are merged by the relaxed decision diagrams,
+//! the nodes of the exact cutsets which are enqueued, and those which are pruned.
+//! It is meant to illustrate the algorithm on tiny instances.
+//!
+//! The solver prunes a node when its bound cannot beat the best value it knows of:
+//! either when the node is selected (the remaining nodes of the frontier are then
+//! pruned as well) or when the exact cutset of a relaxed decision diagram is
+//! enqueued. The solver does not expose that best value, hence the tracer follows
+//! it by watching the compilations. The nodes which cannot improve on the incumbent
+//! of a previous widening phase are pruned when they are pushed onto the frontier.
+//!
+//! The states dropped when a restricted decision diagram is compiled are not
+//! reported by the solver: only the compilation of that diagram is logged.
+
+use std::{fmt::Display, sync::{Mutex, atomic::{AtomicIsize, Ordering}}};
+
+use ddo::{CompilationInput, CompilationType, Completion, Decision, DecisionDiagram, Frontier, Reason, Relaxation, Solution, SubProblem};
+
+/// Where the trace goes
+#[derive(Debug, Clone, Copy)]
+pub struct Tracer {
+    /// Nothing is logged unless this is true
+    pub enabled: bool,
+    /// The factor by which the travel costs are scaled into solver values
+    pub scale: f32,
+}
+
+/// The tracer of the running branch and bound. The solver creates its decision
+/// diagrams by itself (with `Default`), hence they cannot be handed a tracer.
+static ACTIVE: Mutex<Tracer> = Mutex::new(Tracer { enabled: false, scale: 1.0 });
+/// The best value known to the running branch and bound
+static BEST_VALUE: AtomicIsize = AtomicIsize::new(isize::MIN);
+
+impl Tracer {
+    /// Makes this tracer the one of the decision diagrams created from now on. The
+    /// branch and bound which creates them starts without any best value.
+    pub fn install(&self) {
+        *ACTIVE.lock().unwrap() = *self;
+        BEST_VALUE.store(isize::MIN, Ordering::Relaxed);
+    }
+
+    /// Returns the best value known to the running branch and bound
+    fn best_value() -> isize {
+        BEST_VALUE.load(Ordering::Relaxed)
+    }
+
+    fn log(&self, message: impl Display) {
+        if self.enabled {
+            eprintln!("{message}");
+        }
+    }
+
+    /// Converts a value of the solver back into a travel cost (in kilometres)
+    fn cost(&self, value: isize) -> f32 {
+        -(value as f32) / self.scale / 1000.0
+    }
+
+    /// Describes the given node of the frontier
+    fn node<T: Display>(&self, node: &SubProblem<T>) -> String {
+        let path = node.path.iter()
+            .map(|d| d.value.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!("[{path}] {} cost {:.3} bound {:.3}", node.state, self.cost(node.value), self.cost(node.ub))
+    }
+}
+
+/// A frontier which logs the nodes that are pushed, pruned and popped
+pub struct TracingFrontier<'a, F> {
+    pub inner: F,
+    pub tracer: &'a Tracer,
+}
+
+impl <F: Frontier> Frontier for TracingFrontier<'_, F>
+where F::State: Display
+{
+    type State = F::State;
+
+    fn push(&mut self, node: SubProblem<Self::State>) {
+        let before = self.inner.len();
+        let description = if self.tracer.enabled { self.tracer.node(&node) } else { String::new() };
+        self.inner.push(node);
+        if self.inner.len() > before {
+            self.tracer.log(format_args!("  enqueue {description}"));
+        } else {
+            self.tracer.log(format_args!("  prune   {description} (cannot improve on the incumbent)"));
+        }
+    }
+
+    fn pop(&mut self) -> Option<SubProblem<Self::State>> {
+        let node = self.inner.pop();
+        if let Some(node) = node.as_ref().filter(|_| self.tracer.enabled) {
+            let best = Tracer::best_value();
+            if node.ub <= best {
+                self.tracer.log(format_args!("prune   {} and the {} nodes left (their bound cannot beat the best value {:.3})",
+                    self.tracer.node(node), self.inner.len(), self.tracer.cost(best)));
+            } else {
+                self.tracer.log(format_args!("select {} ({} nodes left)", self.tracer.node(node), self.inner.len()));
+            }
+        }
+        node
+    }
+
+    fn clear(&mut self) {
+        self.inner.clear()
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+/// A relaxation which logs the states it merges
+pub struct TracingRelax<'a, R> {
+    pub inner: R,
+    pub tracer: &'a Tracer,
+}
+
+impl <R: Relaxation> Relaxation for TracingRelax<'_, R>
+where R::State: Display
+{
+    type State = R::State;
+
+    fn merge(&self, states: &mut dyn Iterator<Item = &Self::State>) -> Self::State {
+        let states = states.collect::<Vec<_>>();
+        let merged = self.inner.merge(&mut states.iter().copied());
+        self.tracer.log(format_args!("  relax: merge {} states into {merged}", states.len()));
+        merged
+    }
+
+    fn relax(&self, source: &Self::State, dest: &Self::State, new: &Self::State, decision: Decision, cost: isize) -> isize {
+        self.inner.relax(source, dest, new, decision, cost)
+    }
+
+    fn fast_upper_bound(&self, state: &Self::State) -> isize {
+        self.inner.fast_upper_bound(state)
+    }
+}
+
+/// A decision diagram which logs its compilations, and the nodes of its exact
+/// cutset that the solver prunes
+pub struct TracingMdd<D> {
+    inner: D,
+    tracer: Tracer,
+    /// The bound of the node from which the diagram was last compiled
+    residual_ub: isize,
+}
+
+impl <D: Default> Default for TracingMdd<D> {
+    fn default() -> Self {
+        Self { inner: D::default(), tracer: *ACTIVE.lock().unwrap(), residual_ub: isize::MAX }
+    }
+}
+
+impl <D: DecisionDiagram> DecisionDiagram for TracingMdd<D>
+where D::State: Display
+{
+    type State = D::State;
+
+    fn compile(&mut self, input: &CompilationInput<Self::State>) -> Result<Completion, Reason> {
+        if !self.tracer.enabled {
+            return self.inner.compile(input);
+        }
+        let kind = match input.comp_type {
+            CompilationType::Exact      => "exact",
+            CompilationType::Restricted => "restricted",
+            CompilationType::Relaxed    => "relaxed",
+        };
+        self.tracer.log(format_args!("  compile the {kind} decision diagram (width {})", input.max_width));
+        self.residual_ub = input.residual.ub;
+        let completion = self.inner.compile(input)?;
+        // the solver adopts the best value of the restricted diagrams and of the exact ones
+        if input.comp_type == CompilationType::Restricted || completion.is_exact {
+            let value = self.inner.best_value().unwrap_or(isize::MIN);
+            if BEST_VALUE.fetch_max(value, Ordering::Relaxed) < value {
+                self.tracer.log(format_args!("  new best value {:.3}", self.tracer.cost(value)));
+            }
+        }
+        Ok(completion)
+    }
+
+    fn is_exact(&self) -> bool {
+        self.inner.is_exact()
+    }
+
+    fn best_value(&self) -> Option<isize> {
+        self.inner.best_value()
+    }
+
+    fn best_solution(&self) -> Option<Solution> {
+        self.inner.best_solution()
+    }
+
+    fn drain_cutset<F>(&mut self, mut func: F)
+    where F: FnMut(SubProblem<Self::State>)
+    {
+        if !self.tracer.enabled {
+            return self.inner.drain_cutset(func);
+        }
+        let (tracer, ub, best) = (self.tracer, self.residual_ub, Tracer::best_value());
+        self.inner.drain_cutset(|mut node| {
+            // just like the solver, which then only enqueues the nodes whose bound beats its best value
+            node.ub = node.ub.min(ub);
+            if node.ub <= best {
+                tracer.log(format_args!("  prune   {} (its bound cannot beat the best value {:.3})",
+                    tracer.node(&node), tracer.cost(best)));
+            }
+            func(node)
+        })
+    }
+}
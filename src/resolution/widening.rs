@@ -0,0 +1,55 @@
+//! This module implements the iterative widening of the branch and bound: the
+//! search is run several times with geometrically increasing widths, each phase
+//! starting with the best tour of the previous ones as incumbent. Small widths
+//! quickly yield good tours, which then let the larger widths prune more nodes.
+
+use ddo::{Frontier, SubProblem};
+use serde::Serialize;
+
+/// The factor by which the width grows from one phase to the next
+pub const GROWTH: usize = 2;
+
+/// The outcome of one phase of the iterative widening
+#[derive(Debug, Clone, Serialize)]
+pub struct Phase {
+    /// The maximum width of the decision diagrams of this phase
+    pub width: usize,
+    /// The value of the best tour known at the end of this phase (if any)
+    pub best_value: Option<f32>,
+    /// True iff this phase proved the optimality of the best tour
+    pub is_exact: bool,
+    /// The elapsed wall clock time of this phase (in seconds)
+    pub wall_time: f64,
+    /// The number of nodes explored during this phase
+    pub explored_nodes: usize,
+}
+
+/// A frontier which drops the nodes that cannot improve on an incumbent found by
+/// a previous phase (the solver itself does not know about that incumbent)
+pub struct IncumbentFrontier<F> {
+    pub inner: F,
+    /// The value of the best tour found by the previous phases (if any)
+    pub incumbent: Option<isize>,
+}
+
+impl <F: Frontier> Frontier for IncumbentFrontier<F> {
+    type State = F::State;
+
+    fn push(&mut self, node: SubProblem<Self::State>) {
+        if self.incumbent.is_none_or(|best| node.ub > best) {
+            self.inner.push(node)
+        }
+    }
+
+    fn pop(&mut self) -> Option<SubProblem<Self::State>> {
+        self.inner.pop()
+    }
+
+    fn clear(&mut self) {
+        self.inner.clear()
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
@@ -0,0 +1,138 @@
+//! This module provides the random sampling primitives used by the generator. They
+//! are meant to produce bit-identical results on all platforms for a given seed:
+//! - the random bits come from ChaCha20, whose output stream is fully specified,
+//! - the conversion of these bits into floats is done here rather than relying on
+//!   the implementation details of some version of `rand_distr`,
+//! - only the basic IEEE 754 operations (+, -, *, /, sqrt) are used, all of which
+//!   are correctly rounded. In particular, the logarithm needed by the normal
+//!   distribution (and the cosine needed to convert kilometres into degrees of
+//!   longitude) are computed here instead of calling the platform's libm.
+
+use rand::RngCore;
+
+/// Returns a float uniformly sampled in [0, 1) with 53 bits of randomness
+pub fn unit(rng: &mut impl RngCore) -> f64 {
+    (rng.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+/// Returns a float uniformly sampled in [low, high]
+pub fn uniform(rng: &mut impl RngCore, low: f32, high: f32) -> f32 {
+    let (low, high) = (low as f64, high as f64);
+    (low + (high - low) * unit(rng)) as f32
+}
+
+/// Returns a pair of independent samples from the normal distribution having the
+/// given mean and standard deviation (Marsaglia's polar method)
+pub fn normal_pair(rng: &mut impl RngCore, mean: (f32, f32), std_dev: f32) -> (f32, f32) {
+    loop {
+        let u = 2.0 * unit(rng) - 1.0;
+        let v = 2.0 * unit(rng) - 1.0;
+        let s = u * u + v * v;
+        if s > 0.0 && s < 1.0 {
+            let factor = (-2.0 * ln(s) / s).sqrt();
+            let std_dev = std_dev as f64;
+            return (
+                (mean.0 as f64 + std_dev * u * factor) as f32,
+                (mean.1 as f64 + std_dev * v * factor) as f32,
+            );
+        }
+    }
+}
+
+/// A portable cosine of an angle (in radians) between -pi/2 and pi/2, computed by a
+/// fixed number of terms of its Taylor series
+pub fn cos(x: f64) -> f64 {
+    debug_assert!(x.abs() <= std::f64::consts::FRAC_PI_2 + 1e-9);
+    let x2 = x * x;
+    let mut term = 1.0;
+    let mut sum = 0.0;
+    for k in 0..12 {
+        sum += term;
+        term *= -x2 / ((2 * k + 1) * (2 * k + 2)) as f64;
+    }
+    sum
+}
+
+/// A portable natural logarithm of a positive finite number. The argument is split
+/// as x = m * 2^e with m in [1, 2) and ln(m) = 2 atanh((m-1)/(m+1)) is computed by
+/// a fixed number of terms of its series, which converges quickly since the
+/// argument of atanh never exceeds 1/3.
+pub fn ln(x: f64) -> f64 {
+    debug_assert!(x > 0.0 && x.is_finite());
+    const LN_2: f64 = std::f64::consts::LN_2;
+
+    let mut x = x;
+    let mut e = 0_i32;
+    // bring subnormals back to the normal range
+    if x < f64::MIN_POSITIVE {
+        x *= (1_u64 << 54) as f64;
+        e -= 54;
+    }
+    let bits = x.to_bits();
+    e += ((bits >> 52) & 0x7ff) as i32 - 1023;
+    let m = f64::from_bits((bits & !(0x7ff << 52)) | (1023 << 52));
+
+    let z = (m - 1.0) / (m + 1.0);
+    let z2 = z * z;
+    let mut term = z;
+    let mut sum = 0.0;
+    for k in 0..24 {
+        sum += term / (2 * k + 1) as f64;
+        term *= z2;
+    }
+    e as f64 * LN_2 + 2.0 * sum
+}
+
+#[cfg(test)]
+mod tests {
+    //! These fixtures pin the exact bits of the samples drawn for fixed seeds. They
+    //! must pass unchanged on every platform: should one of them ever need to be
+    //! updated, the instances generated from a given seed have changed.
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    use super::*;
+
+    #[test]
+    fn unit_is_pinned() {
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        let bits = (0..4).map(|_| unit(&mut rng).to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits, [4602805363978991273, 4601061104681534936, 4591712652534772064, 4595199839954050212]);
+    }
+
+    #[test]
+    fn uniform_is_pinned() {
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        let bits = (0..4).map(|_| uniform(&mut rng, -4.4744, 8.1350).to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits, [1073773085, 1060285841, 3226430712, 3222749585]);
+    }
+
+    #[test]
+    fn normal_pair_is_pinned() {
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        let bits = (0..3)
+            .map(|_| normal_pair(&mut rng, (4.35, 50.85), 0.1))
+            .map(|(x, y)| (x.to_bits(), y.to_bits()))
+            .collect::<Vec<_>>();
+        assert_eq!(bits, [(1082949086, 1112170035), (1082655916, 1112225699), (1083129794, 1112232353)]);
+    }
+
+    #[test]
+    fn ln_is_pinned() {
+        let inputs = [0.1, 0.5, 1.0, 2.5, 1e-310, 1e300];
+        let bits = inputs.map(|x| ln(x).to_bits());
+        assert_eq!(bits, [13835739416338191637, 13827790571168217583, 0, 4606428432742539383,
+            13872861916001309960, 4649287341619838901]);
+        inputs.iter().for_each(|x| assert!((ln(*x) - x.ln()).abs() <= 1e-12 * x.ln().abs().max(1.0)));
+    }
+
+    #[test]
+    fn cos_is_pinned() {
+        let inputs = [-1.5, -0.75, 0.0, 0.5, 0.8878, std::f64::consts::FRAC_PI_2];
+        let bits = inputs.map(|x| cos(x).to_bits());
+        assert_eq!(bits, [4589761573224315303, 4604765686979698975, 4607182418800017408, 4606079780542709072,
+            4603859843678124023, 4362416445863788464]);
+        inputs.iter().for_each(|x| assert!((cos(*x) - x.cos()).abs() <= 1e-12));
+    }
+}
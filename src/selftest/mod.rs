@@ -0,0 +1,127 @@
+//! This module provides the command which checks that the solver works correctly
+//! on the machine where it has been installed. It solves a small set of instances
+//! which are embedded in the binary and whose optimal values are known (they have
+//! been proven with an exact dynamic programming algorithm, Held-Karp).
+//!
+//! On demand, it also checks the invariants of the solver on random instances:
+//! the cost of the tours it finds must match the one computed by the checker, and
+//! the merges of the relaxed decision diagrams must never cut the optimal tour
+//! (which is verified by solving with a tiny width and comparing with Held-Karp).
+
+use clap::Args;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use serde::Deserialize;
+
+use crate::{instance::Instance, report::Report, check::violations, resolution::{Solve, local_search::tour_cost}};
+
+pub mod random;
+
+/// The bundled instances (in json, along with their optimal value)
+const BUNDLED: [&str; 4] = [
+    include_str!("square-4.json"),
+    include_str!("ring-7.json"),
+    include_str!("clusters-9.json"),
+    include_str!("asymmetric-10.json"),
+];
+
+/// The largest difference (in the units of the instances) that is tolerated
+/// between the value found by the solver and the known optimum
+const TOLERANCE: f32 = 0.5;
+
+/// The width used to solve the random instances, small enough to force merges
+const RANDOM_WIDTH: usize = 2;
+/// The largest number of destinations of a random instance
+const RANDOM_MAX_SIZE: usize = 9;
+
+/// A bundled instance whose optimal value is known
+#[derive(Debug, Deserialize)]
+pub struct Bundled {
+    pub name: String,
+    pub optimum: f32,
+    pub instance: Instance,
+}
+
+/// Returns all the bundled instances
+pub fn bundled() -> Vec<Bundled> {
+    BUNDLED.iter().map(|text| serde_json::from_str(text).unwrap()).collect()
+}
+
+/// This command solves the bundled instances and verifies that the solver proves
+/// their known optimal values (and that the tours it finds are valid).
+#[derive(Debug, Args)]
+pub struct SelfTest {
+    /// The maximum time (in seconds) spent solving each instance
+    #[clap(short, long, default_value="10")]
+    pub timeout: u64,
+    /// The number of random instances on which the invariants of the solver are
+    /// checked (in addition to the bundled instances)
+    #[clap(short, long, default_value="0")]
+    pub random: usize,
+    /// The seed of the random instances
+    #[clap(short, long, default_value="0")]
+    pub seed: u64,
+}
+
+impl SelfTest {
+    /// Executes this command. The process exits with a failure status when the
+    /// solver fails on some instance.
+    pub async fn execute(&self, report: &mut Report) {
+        let solver = Solve { timeout: self.timeout, ..Solve::default() };
+        let mut failures = vec![];
+        for Bundled { name, optimum, instance } in bundled() {
+            let errors = Self::errors(&solver, &instance, optimum);
+            let passed = errors.is_empty();
+            let line = report.messages().format(if passed { "selftest-case-ok" } else { "selftest-case-failed" }, &[("name", &name)]);
+            report.line(line);
+            errors.iter().for_each(|e| report.line(format!("  {e}")));
+            if !passed {
+                failures.push(name);
+            }
+        }
+
+        let mut rng = ChaCha20Rng::seed_from_u64(self.seed);
+        let small = Solve { width: RANDOM_WIDTH, ..solver };
+        for k in 0..self.random {
+            let n = 2 + k % (RANDOM_MAX_SIZE - 1);
+            let instance = random::random_instance(&mut rng, n);
+            let errors = Self::errors(&small, &instance, random::optimum(&instance.costs()));
+            if !errors.is_empty() {
+                let name = format!("random-{k} (seed {}, {n} destinations)", self.seed);
+                let line = report.messages().format("selftest-case-failed", &[("name", &name)]);
+                report.line(line);
+                errors.iter().for_each(|e| report.line(format!("  {e}")));
+                failures.push(name);
+            }
+        }
+        report.json("random", self.random);
+
+        let passed = failures.is_empty();
+        let line = report.messages().text(if passed { "selftest-passed" } else { "selftest-failed" });
+        report.field("passed", passed, line);
+        report.json("failures", &failures);
+        if !passed {
+            report.print();
+            std::process::exit(1);
+        }
+    }
+
+    /// Solves the instance and returns the description of everything that went wrong
+    fn errors(solver: &Solve, instance: &Instance, optimum: f32) -> Vec<String> {
+        let Some(outcome) = solver.solve(instance.clone()) else {
+            return vec!["no tour was found".to_string()];
+        };
+        let mut errors = violations(instance, &outcome.tour);
+        if !outcome.is_exact {
+            errors.push("the optimality of the tour is not proven".to_string());
+        }
+        if (outcome.value - optimum).abs() > TOLERANCE {
+            errors.push(format!("the best value is {} instead of {optimum}", outcome.value));
+        }
+        let cost = tour_cost(&instance.costs(), &outcome.tour);
+        if (cost - outcome.value).abs() > TOLERANCE {
+            errors.push(format!("the tour costs {cost} but its value is {}", outcome.value));
+        }
+        errors
+    }
+}
@@ -0,0 +1,57 @@
+//! This module generates the small random instances on which the selftest checks
+//! the invariants of the solver, along with their optimal value.
+
+use osrm_client::Location;
+use rand::Rng;
+
+use crate::{instance::Instance, sampling, elevation::haversine};
+
+/// Returns a random instance having the given number of destinations. Its travel
+/// costs are (possibly asymmetric) whole numbers of metres, so that the cost of
+/// any tour is computed exactly.
+pub fn random_instance(rng: &mut impl Rng, n: usize) -> Instance {
+    let destinations = (0..n)
+        .map(|_| Location {
+            longitude: sampling::uniform(rng, 2.5, 6.4),
+            latitude: sampling::uniform(rng, 49.5, 51.5),
+        })
+        .collect::<Vec<_>>();
+    let distances = destinations.iter()
+        .map(|a| destinations.iter()
+            .map(|b| (haversine(*a, *b) * (1.0 + sampling::unit(rng) as f32 / 2.0)).round())
+            .collect())
+        .collect();
+    Instance { destinations, distances, ..Instance::default() }
+}
+
+/// Returns the cost of the optimal tour through all the destinations, computed
+/// with the dynamic programming algorithm of Held and Karp
+pub fn optimum(costs: &[Vec<f32>]) -> f32 {
+    let n = costs.len();
+    if n <= 1 {
+        return 0.0;
+    }
+    // best[set][j]: cheapest path leaving the depot, visiting the set and ending in j
+    let full = 1_usize << n;
+    let mut best = vec![vec![f64::INFINITY; n]; full];
+    for j in 1..n {
+        best[1 << j][j] = costs[0][j] as f64;
+    }
+    for set in 1..full {
+        if set & 1 == 1 {
+            continue;
+        }
+        for j in (1..n).filter(|j| set & (1 << j) != 0) {
+            let from = best[set][j];
+            if from.is_infinite() {
+                continue;
+            }
+            for k in (1..n).filter(|k| set & (1 << k) == 0) {
+                let next = &mut best[set | (1 << k)][k];
+                *next = next.min(from + costs[j][k] as f64);
+            }
+        }
+    }
+    let all = full - 2;
+    (1..n).map(|j| best[all][j] + costs[j][0] as f64).fold(f64::INFINITY, f64::min) as f32
+}
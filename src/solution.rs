@@ -0,0 +1,76 @@
+//! This module defines the representation of a solution (tour) to a TSP instance.
+
+use crate::error::ParseError;
+
+/// A tour through the destinations of an instance. The tour always starts at the
+/// depot (destination 0) and implicitly returns to it at the end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    pub tour: Vec<usize>,
+}
+
+impl Solution {
+    /// Parses a sequence of whitespace separated destination identifiers (such as
+    /// the ones printed by the `solve` command). The sequence is rotated so as to
+    /// start at the depot and the explicit return to the depot is dropped if any.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let tour = text.split_whitespace()
+            .map(|tok| tok.parse::<usize>().map_err(|_| ParseError::Token(tok.to_string())))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_sequence(tour))
+    }
+
+    /// Creates a solution from a sequence of destinations which is rotated so as to
+    /// start at the depot (the explicit return to the depot is dropped if any)
+    pub fn from_sequence(mut tour: Vec<usize>) -> Self {
+        if tour.len() > 1 && tour.first() == tour.last() {
+            tour.pop();
+        }
+        if let Some(depot) = tour.iter().position(|x| *x == 0) {
+            tour.rotate_left(depot);
+        }
+        Self { tour }
+    }
+
+    /// Returns the canonical form of this tour: it starts at the depot, and it is
+    /// reversed if need be so that its second destination is smaller than its last
+    /// one. Two tours going along the same cycle (in either direction) thus have
+    /// the same canonical form. Beware that the direction matters when the travel
+    /// costs are asymmetric: this form is meant to compare tours, not to drive them.
+    pub fn canonicalize(self) -> Self {
+        let mut tour = Self::from_sequence(self.tour).tour;
+        if tour.len() > 2 && tour[1] > tour[tour.len() - 1] {
+            tour[1..].reverse();
+        }
+        Self { tour }
+    }
+
+    /// Extracts the tour from the output of a command such as `solve`. That output
+    /// may be a json document, porcelain or human readable lines comprising the
+    /// solution, or simply the sequence of destinations itself.
+    pub fn from_output(text: &str) -> Result<Self, ParseError> {
+        if let Ok(doc) = serde_json::from_str::<serde_json::Value>(text) {
+            if let Some(tour) = doc.get("solution").and_then(|s| s.as_array()) {
+                let tour = tour.iter()
+                    .map(|x| x.as_u64()
+                        .and_then(|x| usize::try_from(x).ok())
+                        .ok_or_else(|| ParseError::Token(x.to_string())))
+                    .collect::<Result<Vec<_>, _>>()?;
+                return Ok(Self::from_sequence(tour));
+            }
+        }
+        let line = text.lines()
+            .find_map(|l| l.strip_prefix("solution:").or_else(|| l.strip_prefix("solution\t")))
+            .unwrap_or(text);
+        Self::parse(line)
+    }
+
+    /// Returns the text representation of this tour, that is the sequence of
+    /// destinations visited after leaving the depot (ending with the return to it)
+    pub fn to_text(&self) -> String {
+        let mut sol = String::new();
+        self.tour.iter().skip(1).chain(std::iter::once(&0))
+            .for_each(|v| sol.push_str(&format!("{v} ")));
+        sol
+    }
+}
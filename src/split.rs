@@ -0,0 +1,115 @@
+//! This module provides the command which splits a long tour into daily tours.
+
+use clap::Args;
+
+use crate::{report::Report, files, check::{self, shift_duration}, visualisation::{self, Palette}, i18n::Messages};
+
+/// This command partitions a tour that is too long for a single shift into daily
+/// tours which all start and end at the depot. The destinations are visited in
+/// the order of the original tour, each day being filled as much as possible.
+#[derive(Debug, Args)]
+pub struct SplitTour {
+    /// The path to the instance file (`-` for the standard input)
+    #[clap(short, long)]
+    pub instance: String,
+    /// The tour to split (sequence of destination identifiers 0..n), or the path
+    /// of a file comprising it (`-` for the standard input)
+    #[clap(short, long)]
+    pub solution: String,
+    /// The maximum working time of one day (in hours)
+    #[clap(short='H', long)]
+    pub max_hours: f32,
+    /// The time (in seconds) spent at each destination (but the depot)
+    #[clap(long, default_value="0")]
+    pub service_time: f32,
+    /// The path of an instance whose matrix holds the travel durations (in seconds)
+    /// between the destinations. It is needed unless the instance was generated
+    /// with --duration.
+    #[clap(long)]
+    pub durations: Option<String>,
+    /// If present, the path where to write an html map showing each day as a
+    /// separate colored route (`-` for the standard output)
+    #[clap(short, long)]
+    pub map: Option<String>,
+    /// The colors of the routes of the successive days
+    #[clap(long, value_enum, default_value_t=Palette::OkabeIto)]
+    pub palette: Palette,
+    /// Overwrite the map file if it already exists
+    #[clap(long)]
+    pub force: bool,
+    /// URL of the osrm server to use (optional)
+    #[clap(short, long)]
+    pub url_osrm: Option<String>,
+}
+
+impl SplitTour {
+    /// Executes this command
+    pub async fn execute(&self, report: &mut Report) {
+        if let Some(map) = self.map.as_ref() {
+            files::check_output(map, self.force);
+        }
+        if self.max_hours.is_nan() || self.max_hours <= 0.0 {
+            clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!(
+                "--max-hours ({}) must be positive\n", self.max_hours))
+                .exit();
+        }
+        let instance = files::read_instance(&self.instance);
+        let tour = files::read_solution(&self.solution).tour;
+        check::ensure_valid(&instance, &tour, &self.solution);
+        let durations = check::durations(&instance, self.durations.as_deref())
+            .unwrap_or_else(|message| clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!("{message}\n")).exit());
+
+        let days = split_days(&durations, &tour, self.service_time, self.max_hours * 3600.0)
+            .unwrap_or_else(|x| clap::Error::raw(clap::error::ErrorKind::ValueValidation, format!(
+                "destination {x} cannot be served within one day of {} hours (--max-hours)\n", self.max_hours))
+                .exit());
+        report.json("instance", &self.instance);
+        report.translated("nb_days", days.len(), "nb-days");
+        for (d, day) in days.iter().enumerate() {
+            let hours = format!("{:.2}", shift_duration(&durations, day, self.service_time) / 3600.0);
+            let stops = day.iter().chain(std::iter::once(&0)).map(|x| x.to_string()).collect::<Vec<_>>().join(" ");
+            let line = report.messages().format("day-summary", &[("day", &(d + 1)), ("stops", &stops), ("hours", &hours)]);
+            report.line(line);
+        }
+        report.json("days", &days);
+
+        if let Some(map) = self.map.as_ref() {
+            let mut client = osrm_client::Client::default();
+            if let Some(url) = self.url_osrm.as_ref() {
+                client = client.base_url(url.clone());
+            }
+            let mut routes = vec![];
+            for day in days.iter() {
+                routes.push(visualisation::solution_route(&client, &instance, day).await);
+            }
+            let html = visualisation::visualize_days(&instance, &routes, &Messages::new(report.lang()), self.palette);
+            files::write_text(map, &html, self.force);
+            report.json("map", map);
+        }
+    }
+}
+
+/// Splits the tour into days lasting at most `max` seconds (service times included).
+/// Each day starts at the depot (and implicitly returns to it). The error is the
+/// first destination which cannot be served within one day, even on its own.
+pub fn split_days(durations: &[Vec<f32>], tour: &[usize], service: f32, max: f32) -> Result<Vec<Vec<usize>>, usize> {
+    let mut days: Vec<Vec<usize>> = vec![];
+    let mut day = vec![0];
+    let mut elapsed = 0.0;
+    for x in tour.iter().copied().filter(|x| *x != 0) {
+        let last = *day.last().unwrap();
+        if elapsed + durations[last][x] + service + durations[x][0] > max && day.len() > 1 {
+            days.push(day);
+            day = vec![0];
+            elapsed = 0.0;
+        }
+        let last = *day.last().unwrap();
+        elapsed += durations[last][x] + service;
+        if elapsed + durations[x][0] > max {
+            return Err(x);
+        }
+        day.push(x);
+    }
+    days.push(day);
+    Ok(days)
+}
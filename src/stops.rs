@@ -0,0 +1,152 @@
+//! This module parses the sets of real stops (locations) which some commands
+//! take as a reference, e.g. to fit the parameters of the generator. It does not
+//! touch the file system, which makes it possible to fuzz it.
+//!
+//! Datasets often come with their longitude and latitude columns swapped. When
+//! the swapped coordinates are out of range, this is obvious. In many regions
+//! however (e.g. Belgium, where the longitudes are valid latitudes and vice
+//! versa), both orders are valid: the order which is retained is then the one
+//! whose stops lie within the region where the stops are expected.
+
+use osrm_client::Location;
+
+use crate::{instance::Instance, error::ParseError};
+
+/// The region which is expected to contain the stops by default: the default box
+/// of the generator
+pub const DEFAULT_REGION: &str = "-4.4744,42.1958,8.1350,51.0521";
+
+/// A region (bounding box) which is expected to contain the stops
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub min_longitude: f32,
+    pub min_latitude: f32,
+    pub max_longitude: f32,
+    pub max_latitude: f32,
+}
+
+impl Region {
+    /// Parses a region given as `min_longitude,min_latitude,max_longitude,max_latitude`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let bounds = text.split(',')
+            .map(|c| c.trim().parse::<f32>().map_err(|e| format!("{c} is not a number: {e}")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let [min_longitude, min_latitude, max_longitude, max_latitude] = bounds[..] else {
+            return Err(format!("{text} is not of the form min_longitude,min_latitude,max_longitude,max_latitude"));
+        };
+        if min_longitude >= max_longitude || min_latitude >= max_latitude {
+            return Err(format!("{text} is empty: the minima must be smaller than the maxima"));
+        }
+        Ok(Region { min_longitude, min_latitude, max_longitude, max_latitude })
+    }
+
+    /// Returns true iff the region contains the given location
+    pub fn contains(&self, location: Location) -> bool {
+        (self.min_longitude..=self.max_longitude).contains(&location.longitude)
+            && (self.min_latitude..=self.max_latitude).contains(&location.latitude)
+    }
+}
+
+/// Returns the given location, whose longitude and latitude have been exchanged
+fn swapped(location: &Location) -> Location {
+    Location { longitude: location.latitude, latitude: location.longitude }
+}
+
+/// Returns true iff the coordinates of the given location are within range
+fn in_range(location: &Location) -> bool {
+    location.longitude.abs() <= 180.0 && location.latitude.abs() <= 90.0
+}
+
+/// Returns the stops with their coordinates in the right order, along with a flag
+/// telling whether they had to be swapped. They are swapped when only the swapped
+/// coordinates are within range, or when both orders are within range but more
+/// of the swapped stops lie in the given region. The error tells which stop is out
+/// of range in both orders.
+pub fn orient(stops: Vec<Location>, region: &Region) -> Result<(Vec<Location>, bool), ParseError> {
+    let as_is = stops.iter().all(in_range);
+    let reversed = stops.iter().map(swapped).all(|s| in_range(&s));
+    let fit = |f: &dyn Fn(&Location) -> Location| stops.iter().filter(|s| region.contains(f(s))).count();
+    let swap = match (as_is, reversed) {
+        (true, true)   => fit(&swapped) > fit(&|s| *s),
+        (false, true)  => true,
+        (true, false)  => false,
+        (false, false) => {
+            let (k, s) = stops.iter().enumerate().find(|(_, s)| !in_range(s)).unwrap();
+            return Err(ParseError::Invalid(format!("stop {} has out of range coordinates (longitude {}, latitude {})",
+                k + 1, s.longitude, s.latitude)));
+        },
+    };
+    if swap {
+        Ok((stops.iter().map(swapped).collect(), true))
+    } else {
+        Ok((stops, false))
+    }
+}
+
+/// Parses the stops of a text in any of the formats accepted by `files::read_stops`,
+/// without checking the ranges of their coordinates
+pub fn parse(text: &str) -> Result<Vec<Location>, ParseError> {
+    if text.trim_start().starts_with('{') {
+        let doc: serde_json::Value = serde_json::from_str(text)?;
+        if doc["type"] != "FeatureCollection" {
+            return Ok(Instance::from_json(text)?.destinations);
+        }
+        let stops = doc["features"].as_array().into_iter().flatten()
+            .map(|f| &f["geometry"])
+            .filter(|g| g["type"] == "Point")
+            .filter_map(|g| Some(Location {
+                longitude: g["coordinates"][0].as_f64()? as f32,
+                latitude: g["coordinates"][1].as_f64()? as f32,
+            }))
+            .collect::<Vec<_>>();
+        if stops.is_empty() {
+            return Err(ParseError::Invalid("the feature collection has no point".to_string()));
+        }
+        return Ok(stops);
+    }
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty()).peekable();
+    let split = |line: &str| line.split([',', ';', '\t']).map(|c| c.trim().trim_matches('"').to_lowercase()).collect::<Vec<_>>();
+    let header = lines.peek().map(|l| split(l)).unwrap_or_default();
+    let column = |names: &[&str]| header.iter().position(|h| names.contains(&h.as_str()));
+    let (lon, lat) = match (column(&["lon", "lng", "long", "longitude"]), column(&["lat", "latitude"])) {
+        (Some(lon), Some(lat)) => {
+            lines.next();
+            (lon, lat)
+        },
+        _ => (0, 1),
+    };
+
+    let mut stops = vec![];
+    for (k, line) in lines.enumerate() {
+        let cells = split(line);
+        let coordinate = |c: usize| cells.get(c)
+            .and_then(|v| v.parse::<f32>().ok())
+            .ok_or_else(|| ParseError::Invalid(format!("line {} has no valid coordinates: {line}", k + 1)));
+        stops.push(Location { longitude: coordinate(lon)?, latitude: coordinate(lat)? });
+    }
+    if stops.is_empty() {
+        return Err(ParseError::Invalid("there is no stop".to_string()));
+    }
+    Ok(stops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swapped_belgian_stops_are_exchanged_back() {
+        // Brussels, Liège and Namur with the latitude in the longitude column
+        let text = "lon,lat\n50.8467,4.3525\n50.6326,5.5797\n50.4674,4.8720\n";
+        let region = Region::parse(DEFAULT_REGION).unwrap();
+        let (stops, swapped) = orient(parse(text).unwrap(), &region).unwrap();
+        assert!(swapped);
+        assert_eq!((stops[0].longitude, stops[0].latitude), (4.3525, 50.8467));
+        assert!(stops.iter().all(|s| region.contains(*s)));
+
+        let text = "lon,lat\n4.3525,50.8467\n5.5797,50.6326\n4.8720,50.4674\n";
+        let (stops, swapped) = orient(parse(text).unwrap(), &region).unwrap();
+        assert!(!swapped);
+        assert_eq!((stops[0].longitude, stops[0].latitude), (4.3525, 50.8467));
+    }
+}
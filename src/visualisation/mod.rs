@@ -1,72 +1,183 @@
 //! This module implements the visualisation facilities that can be used to generate an
 //! html file depicting the instance (and a possible solution of that instance).
-use std::{io::{BufReader, Write}, fs::File};
+
+use std::{path::Path, fs, time::Duration};
 
 use clap::Args;
-use handlebars::no_escape;
-use osrm_client::{Route, RouteRequestBuilder, Geometries, OverviewRequest, Client};
+use handlebars::{no_escape, Handlebars};
+use osrm_client::{Route, RouteRequestBuilder, Geometries, OverviewRequest, Client, Location};
 use rand_distr::num_traits::ToPrimitive;
-use serde_json::json;
+use serde_json::{json, Value};
+
+use crate::{instance::Instance, report::Report, files, project::Project, elevation, footprint::Footprint, i18n::Messages};
+
+pub use self::palette::Palette;
+pub use self::thumbnail::thumbnail;
+
+mod palette;
+mod thumbnail;
+
+/// The ids of the messages which are used as labels in the html pages
+const LABELS: [&str; 14] = ["distance-unit", "cost", "co2", "day", "destination", "elevation", "legend",
+    "status-unchanged", "status-moved", "status-added", "status-removed", "stop", "map-description",
+    "profile-description"];
 
-use crate::instance::Instance;
+/// The number of points of the elevation profile of a route
+const PROFILE_SAMPLES: usize = 200;
 
 /// This command lets you generate an html file to visualize a given instance
 /// and an optional solution.
 #[derive(Debug, Args)]
 pub struct Visualize {
-    /// The path to the instance file
-    #[clap(short, long)]
-    pub instance: String,
-    /// A possible solution (sequence of destination identifiers 0..n)
+    /// The path to the instance file (`-` for the standard input)
+    #[clap(short, long, required_unless_present="project")]
+    pub instance: Option<String>,
+    /// A possible solution (sequence of destination identifiers 0..n), or the path
+    /// of a file comprising it (`-` for the standard input)
     #[clap(short, long)]
     pub solution: Option<String>,
-    /// If present, the path where to write the output html
+    /// If present, the path where to write the output html (`-` for the standard output)
     #[clap(short, long)]
     pub output: Option<String>,
+    /// Overwrite the output file if it already exists
+    #[clap(long)]
+    pub force: bool,
     /// URL of the osrm server to use (optional)
     #[clap(short, long)]
     pub url_osrm: Option<String>,
+    /// If present, the directory of the project whose conventionally named files
+    /// are used as default inputs and outputs of this command
+    #[clap(short='P', long)]
+    pub project: Option<String>,
+    /// If present, the solution file to watch: the html is regenerated each time
+    /// that file changes, and the page reloads itself in the browser
+    #[clap(short, long, conflicts_with="solution")]
+    pub watch: Option<String>,
+    /// How often (in seconds) the watched file is checked for changes
+    #[clap(long, default_value="2")]
+    pub watch_interval: u64,
+    /// If present, the path of another version of the instance (e.g. a perturbed
+    /// one). The map then shows the destinations which have been moved, added or
+    /// removed to go from the instance to that other version.
+    #[clap(long, conflicts_with_all=["solution", "watch"])]
+    pub diff: Option<String>,
+    /// If present, the URL of an elevation service (Open-Elevation api) used to
+    /// draw the elevation profile of the route under the map
+    #[clap(long)]
+    pub elevation_url: Option<String>,
+    /// The colors used to tell the clusters (or the statuses of a diff) apart
+    #[clap(long, value_enum, default_value_t=Palette::OkabeIto)]
+    pub palette: Palette,
+    #[clap(flatten)]
+    pub footprint: Footprint,
 }
 impl Visualize {
     /// Executes this command
-    pub async fn execute(&self) {
-        let instance = serde_json::from_reader(BufReader::new(File::open(&self.instance).unwrap())).unwrap();
+    pub async fn execute(&self, report: &mut Report) {
+        let project = self.project.as_deref().map(Project::open);
+        let instance_path = self.instance.clone().or_else(|| project.as_ref().map(Project::instance)).unwrap();
+        let solution = self.solution.clone().or_else(|| project.as_ref()
+            .map(Project::solution)
+            .filter(|s| Path::new(s).is_file()));
+        let instance = files::read_instance(&instance_path);
+        let output = self.output.clone().or_else(|| project.as_ref().map(Project::map));
+        if let Some(output) = output.as_ref().filter(|_| self.watch.is_none()) {
+            files::check_output(output, self.force);
+        }
+        let messages = Messages::new(report.lang());
+
+        if let Some(watched) = self.watch.as_ref() {
+            let output = output.unwrap_or_else(|| clap::Error::raw(clap::error::ErrorKind::MissingRequiredArgument,
+                "--watch needs an output file to regenerate: use --output or --project\n").exit());
+            self.watch(&instance, watched, &output, &messages).await;
+            return;
+        }
         
-        let html = if let Some(solution) = self.solution.as_ref() {
-            let mut client = osrm_client::Client::default();
-            if let Some(url) = self.url_osrm.as_ref() {
-                client = client.base_url(url.clone());
+        let html = if let Some(other) = self.diff.as_ref() {
+            let other = files::read_instance(other);
+            let changes = Self::changes(&instance, &other);
+            for (status, count) in changes.iter() {
+                let line = format!("{} {count}", messages.text(&format!("status-{status}")));
+                report.field(status, count, line);
             }
-            let solution = solution.split_whitespace().into_iter().map(|tok| tok.parse::<usize>().unwrap()).collect::<Vec<_>>();
-            let route = self.solution_route(&client, &instance, &solution).await;
-            self.visualize_solution(&instance, &route).await
+            self.visualize_diff(&instance, &other, &messages)
         } else {
-            self.visualize(&instance).await
+            self.render(&instance, solution.as_deref(), &messages).await
         };
         
-        if let Some(output) = self.output.as_ref() {
-            File::create(output).unwrap().write_all(html.as_bytes()).unwrap();
+        report.json("instance", &instance_path);
+        report.json("solution", &solution);
+        report.json("diff", &self.diff);
+        if let Some(output) = output.as_ref() {
+            files::write_text(output, &html, self.force);
+            report.json("output", output);
+        } else {
+            report.field("html", &html, html.clone());
+        }
+        if let Some(project) = project {
+            project.log_command("visualize", report.document());
+        }
+    }
+
+    /// Renders the html depicting the instance and the (optional) solution
+    async fn render(&self, instance: &Instance, solution: Option<&str>, messages: &Messages) -> String {
+        if let Some(solution) = solution {
+            let mut client = osrm_client::Client::default();
+            if let Some(url) = self.url_osrm.as_ref() {
+                client = client.base_url(url.clone());
+            }
+            let solution = files::read_solution(solution).tour;
+            let route = solution_route(&client, instance, &solution).await;
+            self.visualize_solution(instance, &solution, &route, messages).await
         } else {
-            println!("{html}");
+            self.visualize(instance, messages).await
         }
     }
 
+    /// Regenerates the output html whenever the watched solution file changes.
+    /// This never returns: it is meant to be interrupted by the user.
+    async fn watch(&self, instance: &Instance, watched: &str, output: &str, messages: &Messages) {
+        let mut last_change = None;
+        loop {
+            let change = fs::metadata(watched).and_then(|m| m.modified()).ok();
+            if change.is_some() && change != last_change {
+                last_change = change;
+                let html = self.render(instance, Some(watched), messages).await;
+                files::write_text(output, &html, true);
+                eprintln!("updated {output}");
+            }
+            tokio::time::sleep(Duration::from_secs(self.watch_interval)).await;
+        }
+    }
+
+    /// Returns the number of seconds after which the page must reload itself (if any)
+    fn refresh(&self) -> Option<u64> {
+        self.watch.as_ref().map(|_| self.watch_interval)
+    }
+
     /// Bare bones visualisation: only shows the locations on the map
-    pub async fn visualize(&self, instance: &Instance) -> String {
-        let template = include_str!("./visual_template.hbs");
-        let destinations = serde_json::to_string_pretty(&instance.geojson()).unwrap();
-        let handlebars = handlebars::Handlebars::new();
-        handlebars.render_template(template, &json!({
-            "destinations": destinations
-        })).unwrap()
+    pub async fn visualize(&self, instance: &Instance, messages: &Messages) -> String {
+        let order = (0..instance.len()).collect::<Vec<_>>();
+        render_page(instance, &order, messages, self.palette, json!({
+            "refresh": self.refresh(),
+        }))
     }
 
     /// More elaborate visualisation: shows locations as well as a route to join all these cities
-    pub async fn visualize_solution(&self, instance: &Instance, route: &Route) -> String {
-        let template = include_str!("./visual_template.hbs");
+    pub async fn visualize_solution(&self, instance: &Instance, tour: &[usize], route: &Route, messages: &Messages) -> String {
         let total_distance = route.distance;
         let total_duration = route.duration;
-        let destinations = serde_json::to_string(&instance.geojson()).unwrap();
+        let profile = if let Some(url) = self.elevation_url.as_ref() {
+            match elevation::profile(url, &Self::route_path(route), PROFILE_SAMPLES).await {
+                Ok(profile) => Some(serde_json::to_string(&profile).unwrap()),
+                Err(message) => {
+                    eprintln!("warning: {message}: the map has no elevation profile");
+                    None
+                },
+            }
+        } else {
+            None
+        };
         let route = serde_json::to_string(&route.geometry).unwrap();
 
         let hours = total_duration / 3600.0;
@@ -78,29 +189,216 @@ impl Visualize {
         let seconds = seconds.floor().to_u8().unwrap();
 
 
-        let mut handlebars = handlebars::Handlebars::new();
-        handlebars.register_escape_fn(no_escape);
-        handlebars.render_template(template, &json!({
-            "destinations": destinations,
+        let km = total_distance / 1000.0;
+        let cost = self.footprint.cost(km).map(|c| format!("{c:.2}"));
+        let co2 = self.footprint.co2(km).map(|c| format!("{c:.2}"));
+
+        render_page(instance, tour, messages, self.palette, json!({
             "route": route,
+            "cost": cost,
+            "co2": co2,
             "totalDistance": format!("{:.2}", total_distance / 1000.0),   // in kilometers
-            "totalDuration": format!("{hours} hours {minutes} minutes {seconds} seconds"), // in hours
+            "totalDuration": messages.format("duration", &[("hours", &hours), ("minutes", &minutes), ("seconds", &seconds)]),
+            "refresh": self.refresh(),
+            "profile": profile,
+        }))
+    }
+
+    /// Returns the status of the destination at the given index when going from the
+    /// instance `before` to the instance `after`
+    fn status(before: &Instance, after: &Instance, i: usize) -> &'static str {
+        match (before.destinations.get(i), after.destinations.get(i)) {
+            (Some(b), Some(a)) if b.longitude == a.longitude && b.latitude == a.latitude => "unchanged",
+            (Some(_), Some(_)) => "moved",
+            (None, _) => "added",
+            (_, None) => "removed",
+        }
+    }
+
+    /// Counts the destinations having each status between the two instances
+    fn changes(before: &Instance, after: &Instance) -> Vec<(&'static str, usize)> {
+        let n = before.len().max(after.len());
+        ["unchanged", "moved", "added", "removed"].into_iter()
+            .map(|status| (status, (0..n).filter(|i| Self::status(before, after, *i) == status).count()))
+            .collect()
+    }
+
+    /// Visualisation of the differences between two versions of an instance: the
+    /// destinations are color coded according to their status and an arrow joins
+    /// the former and new positions of each moved destination
+    pub fn visualize_diff(&self, before: &Instance, after: &Instance, messages: &Messages) -> String {
+        let point = |l: &Location| json!([l.longitude, l.latitude]);
+        let n = before.len().max(after.len());
+        let mut features: Vec<Value> = vec![];
+        for i in 0..n {
+            let status = Self::status(before, after, i);
+            let location = after.destinations.get(i).or(before.destinations.get(i)).unwrap();
+            features.push(json!({
+                "type": "Feature",
+                "properties": {"id": i, "status": status},
+                "geometry": {"type": "Point", "coordinates": point(location)},
+            }));
+            if status == "moved" {
+                features.push(json!({
+                    "type": "Feature",
+                    "properties": {"id": i, "status": "move"},
+                    "geometry": {"type": "LineString", "coordinates": [
+                        point(&before.destinations[i]), point(&after.destinations[i])
+                    ]},
+                }));
+            }
+        }
+        let diff = serde_json::to_string(&json!({"type": "FeatureCollection", "features": features})).unwrap();
+
+        render_page(before, &[], messages, self.palette, json!({
+            "diff": diff,
+        }))
+    }
+
+    /// Returns the sequence of locations along the geometry of the given route
+    fn route_path(route: &Route) -> Vec<Location> {
+        let geometry = serde_json::to_value(&route.geometry).unwrap();
+        geometry["coordinates"].as_array().into_iter().flatten()
+            .filter_map(|point| Some(Location {
+                longitude: point.get(0)?.as_f64()? as f32,
+                latitude: point.get(1)?.as_f64()? as f32,
+            }))
+            .collect()
+    }
+}
+
+/// Computes the actual route based on the locations ordering. Just like the tour
+/// itself, the route returns to its first destination (the depot).
+pub async fn solution_route(client: &Client, instance: &Instance, solution: &[usize]) -> Route {
+    let path = solution.iter().chain(solution.first()).copied()
+            .map(|i| instance.destinations[i])
+            .collect();
+    let response = RouteRequestBuilder::default()
+        .coordinates(osrm_client::Coordinates::Multi(path))
+        .geometries(Geometries::GeoJson)
+        .overview(OverviewRequest::Full)
+        .build()
+        .unwrap()
+        .send(client).await
+        .unwrap();
+    response.routes[0].clone()
+}
+
+/// Visualisation of a multi-day tour: the route of each day (which starts and ends
+/// at the depot) is drawn with its own color
+pub fn visualize_days(instance: &Instance, days: &[Route], messages: &Messages, palette: Palette) -> String {
+    let days = days.iter().enumerate()
+        .map(|(d, route)| json!({
+            "geometry": route.geometry,
+            "color": palette.color(d),
+            "label": format!("{} {}: {:.2} {}", messages.text("day"), d + 1, route.distance / 1000.0, messages.text("distance-unit")),
+        }))
+        .collect::<Vec<_>>();
+
+    let order = (0..instance.len()).collect::<Vec<_>>();
+    render_page(instance, &order, messages, palette, json!({
+        "days": serde_json::to_string(&days).unwrap(),
+    }))
+}
+
+/// Renders the html page. The given context completes the one which is common to
+/// all the pages: the destinations, the stops (which can be browsed with the
+/// keyboard in the given order), the labels and the colors.
+fn render_page(instance: &Instance, order: &[usize], messages: &Messages, palette: Palette, context: Value) -> String {
+    let template = include_str!("./visual_template.hbs");
+    let stops = order.iter()
+        .map(|i| json!({
+            "id": i,
+            "longitude": instance.destinations[*i].longitude,
+            "latitude": instance.destinations[*i].latitude,
+            "color": palette.color(instance.clusters.get(*i).copied().unwrap_or(0)),
+        }))
+        .collect::<Vec<_>>();
+
+    let mut page = json!({
+        "lang": messages.lang().code(),
+        "destinations": serde_json::to_string(&instance.geojson()).unwrap(),
+        "stops": serde_json::to_string(&stops).unwrap(),
+        "palette": serde_json::to_string(palette.colors()).unwrap(),
+        "routeColor": palette.route(),
+        "labels": messages.labels(&LABELS),
+    });
+    if let (Some(page), Value::Object(context)) = (page.as_object_mut(), context) {
+        page.extend(context);
+    }
+
+    let mut handlebars = Handlebars::new();
+    handlebars.register_escape_fn(no_escape);
+    handlebars.render_template(template, &page).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::FromArgMatches;
+
+    use crate::i18n::Lang;
+    use super::*;
+
+    /// Returns the command configured by the given command line arguments
+    fn visualizer(args: &[&str]) -> Visualize {
+        let command = Visualize::augment_args(clap::Command::new("visualize"));
+        let matches = command.get_matches_from(["visualize", "--instance", files::STDIO].into_iter().chain(args.iter().copied()));
+        Visualize::from_arg_matches(&matches).unwrap()
+    }
+
+    /// Three destinations of Brussels, in two clusters
+    fn instance() -> Instance {
+        Instance::from_json(r#"{
+            "destinations": [
+                {"longitude": 4.3517, "latitude": 50.8503},
+                {"longitude": 4.3601, "latitude": 50.8467},
+                {"longitude": 4.3925, "latitude": 50.8398}
+            ],
+            "distances": [[0, 820, 3110], [840, 0, 2500], [3090, 2460, 0]],
+            "clusters": [0, 0, 1]
+        }"#).unwrap()
+    }
+
+    /// The route which the routing service would return for the tour 0 1 2 0
+    fn route(distance: f32) -> Route {
+        serde_json::from_value(json!({
+            "distance": distance,
+            "duration": 734.5,
+            "weight": 734.5,
+            "weight_name": "routability",
+            "geometry": {"type": "LineString", "coordinates": [
+                [4.3517, 50.8503], [4.3601, 50.8467], [4.3925, 50.8398], [4.3517, 50.8503]
+            ]},
+            "legs": []
         })).unwrap()
     }
 
-    /// Computes the actual route based on the locations ordering
-    async fn solution_route(&self, client: &Client, instance: &Instance, solution: &[usize]) -> Route {
-        let path = solution.iter().copied()
-                .map(|i| instance.destinations[i])
-                .collect();
-        let response = RouteRequestBuilder::default()
-            .coordinates(osrm_client::Coordinates::Multi(path))
-            .geometries(Geometries::GeoJson)
-            .overview(OverviewRequest::Full)
-            .build()
-            .unwrap()
-            .send(client).await
-            .unwrap();
-        response.routes[0].clone()
-    }
-}
\ No newline at end of file
+    #[test]
+    fn page() {
+        let html = render_page(&instance(), &[0, 2, 1], &Messages::new(Lang::En), Palette::OkabeIto, json!({}));
+        insta::assert_snapshot!(html);
+    }
+
+    #[tokio::test]
+    async fn solution() {
+        let visualizer = visualizer(&["--cost-per-km", "0.5"]);
+        let html = visualizer.visualize_solution(&instance(), &[0, 1, 2], &route(6430.0), &Messages::new(Lang::Fr)).await;
+        insta::assert_snapshot!(html);
+    }
+
+    #[test]
+    fn diff() {
+        let before = instance();
+        let mut after = instance();
+        after.destinations[1].latitude = 50.8471;
+        after.destinations.pop();
+        let html = visualizer(&[]).visualize_diff(&before, &after, &Messages::new(Lang::En));
+        insta::assert_snapshot!(html);
+    }
+
+    #[test]
+    fn days() {
+        let html = visualize_days(&instance(), &[route(6430.0), route(2120.0)], &Messages::new(Lang::Nl), Palette::OkabeIto);
+        insta::assert_snapshot!(html);
+    }
+}
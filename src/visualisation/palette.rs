@@ -0,0 +1,42 @@
+//! This module defines the color palettes of the html maps.
+
+use clap::ValueEnum;
+
+/// The sets of colors used to tell apart the clusters, the days of a tour or the
+/// statuses of destinations on a map
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Palette {
+    /// The Okabe-Ito palette, which remains distinguishable with all common forms
+    /// of color blindness
+    #[default]
+    OkabeIto,
+    /// Paul Tol's bright palette, which is also color blind safe
+    Tol,
+    /// The colors used by the former versions of the maps (not color blind safe)
+    Classic,
+}
+
+impl Palette {
+    /// Returns the colors of this palette
+    pub fn colors(&self) -> &'static [&'static str] {
+        match self {
+            Palette::OkabeIto => &["#0072B2", "#E69F00", "#009E73", "#D55E00", "#56B4E9", "#CC79A7", "#F0E442"],
+            Palette::Tol      => &["#4477AA", "#EE6677", "#228833", "#CCBB44", "#66CCEE", "#AA3377", "#BBBBBB"],
+            Palette::Classic  => &["#3366ff", "#ff9900", "#33aa33", "#dd2222", "#911eb4", "#42d4f4", "#f032e6"],
+        }
+    }
+
+    /// Returns the i-th color of this palette (cycling through the colors)
+    pub fn color(&self, i: usize) -> &'static str {
+        let colors = self.colors();
+        colors[i % colors.len()]
+    }
+
+    /// Returns the color of a route (which contrasts with all the other colors)
+    pub fn route(&self) -> &'static str {
+        match self {
+            Palette::Classic => "red",
+            _ => "#000000",
+        }
+    }
+}
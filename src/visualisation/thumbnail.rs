@@ -0,0 +1,51 @@
+//! This module draws a tiny static picture of the destinations of an instance (an
+//! svg document), which requires neither a network connection nor a browser.
+
+use std::fmt::Write;
+
+use crate::instance::Instance;
+
+use super::Palette;
+
+/// The size (in pixels) of the thumbnail
+const SIZE: f32 = 200.0;
+/// The margin (in pixels) around the destinations
+const MARGIN: f32 = 8.0;
+
+/// Returns the svg thumbnail of the given instance: each destination is a dot having
+/// the color of its cluster, the depot is a black square, and the centroids (if
+/// known) are crosses. The longitudes are scaled by the cosine of the mean latitude
+/// so that the clusters keep their shape.
+pub fn thumbnail(instance: &Instance, palette: Palette) -> String {
+    let points = instance.destinations.iter().chain(instance.metadata.centroids.iter());
+    let (mut min_x, mut max_x, mut min_y, mut max_y) = (f32::INFINITY, f32::NEG_INFINITY, f32::INFINITY, f32::NEG_INFINITY);
+    let mean_latitude = instance.destinations.iter().map(|d| d.latitude).sum::<f32>() / instance.len().max(1) as f32;
+    let shrink = mean_latitude.to_radians().cos();
+    for p in points {
+        min_x = min_x.min(p.longitude * shrink);
+        max_x = max_x.max(p.longitude * shrink);
+        min_y = min_y.min(p.latitude);
+        max_y = max_y.max(p.latitude);
+    }
+    let scale = (SIZE - 2.0 * MARGIN) / (max_x - min_x).max(max_y - min_y).max(1e-6);
+    // the north is up, hence the y axis is flipped
+    let project = |longitude: f32, latitude: f32| (MARGIN + (longitude * shrink - min_x) * scale, SIZE - MARGIN - (latitude - min_y) * scale);
+
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{SIZE}\" height=\"{SIZE}\" viewBox=\"0 0 {SIZE} {SIZE}\">\n");
+    writeln!(svg, "  <rect width=\"{SIZE}\" height=\"{SIZE}\" fill=\"white\" stroke=\"#cccccc\"/>").unwrap();
+    for (i, d) in instance.destinations.iter().enumerate().skip(1) {
+        let (x, y) = project(d.longitude, d.latitude);
+        let color = palette.color(instance.clusters.get(i).copied().unwrap_or(0));
+        writeln!(svg, "  <circle cx=\"{x:.1}\" cy=\"{y:.1}\" r=\"2.5\" fill=\"{color}\"/>").unwrap();
+    }
+    for c in instance.metadata.centroids.iter() {
+        let (x, y) = project(c.longitude, c.latitude);
+        writeln!(svg, "  <path d=\"M{:.1} {:.1}h8M{:.1} {:.1}v8\" stroke=\"#555555\"/>", x - 4.0, y, x, y - 4.0).unwrap();
+    }
+    if let Some(depot) = instance.destinations.first() {
+        let (x, y) = project(depot.longitude, depot.latitude);
+        writeln!(svg, "  <rect x=\"{:.1}\" y=\"{:.1}\" width=\"6\" height=\"6\" fill=\"black\"/>", x - 3.0, y - 3.0).unwrap();
+    }
+    svg.push_str("</svg>\n");
+    svg
+}